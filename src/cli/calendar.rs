@@ -0,0 +1,230 @@
+use std::collections::BTreeMap;
+
+use ansi_term::Style;
+use chrono::{Datelike, NaiveDate, TimeDelta, Weekday};
+
+use crate::color::{colors_enabled, styled, CliStyles};
+use crate::stats::{days_into_week, level, month_weeks, week_header, DayStatus};
+
+/// Room taken by the weekday labels in front of the heatmap
+const LABEL_WIDTH: usize = 4;
+const CELL: &str = "■";
+/// Cells by level for when there's no color to tell them apart
+const ASCII_CELLS: [&str; 5] = [".", ":", "o", "O", "#"];
+
+/// A heatmap cell, colored by level or drawn as a shade of ASCII
+fn cell(cli_styles: &CliStyles, level: usize) -> String {
+    match colors_enabled() {
+        true => styled(Style::new().fg(cli_styles.heatmap[level]))
+            .paint(CELL)
+            .to_string(),
+        false => ASCII_CELLS[level].to_string(),
+    }
+}
+
+/// Builds a year of check-ins as a grid with a column per week, colored by
+/// the ratio of check-ins on each day. Cells are two columns wide when they
+/// fit in `width`, then one, and if a year still doesn't fit only the most
+/// recent weeks up to `today` are shown. Rows start on `week_start`.
+pub fn build_heatmap(
+    year: i32,
+    ratios: &BTreeMap<NaiveDate, f64>,
+    today: NaiveDate,
+    width: usize,
+    week_start: Weekday,
+) -> String {
+    let cli_styles = CliStyles::new();
+    let first = NaiveDate::from_ymd_opt(year, 1, 1).unwrap_or(today);
+    let last = NaiveDate::from_ymd_opt(year, 12, 31).unwrap_or(today);
+    let first_week = first - TimeDelta::days(days_into_week(first.weekday(), week_start) as i64);
+    let weeks = ((last - first_week).num_days() / 7 + 1) as usize;
+
+    let room = width.saturating_sub(LABEL_WIDTH);
+    let cell_width = match room >= weeks * 2 {
+        true => 2,
+        false => 1,
+    };
+    // Keep the weeks leading up to today when the whole year doesn't fit
+    let end_week = match today >= first && today <= last {
+        true => ((today - first_week).num_days() / 7 + 1) as usize,
+        false => weeks,
+    };
+    let shown = (room / cell_width).min(weeks).max(1);
+    let start_week = match weeks > shown {
+        true => end_week.saturating_sub(shown),
+        false => 0,
+    };
+    let week_range = start_week..(start_week + shown).min(weeks);
+
+    let mut lines = vec![];
+    let mut months = " ".repeat(LABEL_WIDTH);
+    let mut column = 0;
+    for week in week_range.clone() {
+        let week_begins = first_week + TimeDelta::weeks(week as i64);
+        let starts_month = (0..7)
+            .map(|d| week_begins + TimeDelta::days(d))
+            .find(|day| day.day() == 1 && day.year() == year);
+        let at = (week - start_week) * cell_width;
+        if let Some(day) = starts_month {
+            if at >= column {
+                months.push_str(&" ".repeat(at - column));
+                let name = day.format("%b").to_string();
+                column = at + name.len();
+                months.push_str(&name);
+            }
+        }
+    }
+    lines.push(months.trim_end().to_string());
+
+    // Label every other row
+    let header = week_header(week_start);
+    let labels = header
+        .split(' ')
+        .enumerate()
+        .map(|(i, name)| if i % 2 == 0 { name } else { "" });
+    for (weekday, label) in labels.enumerate() {
+        let mut line = format!("{label:<LABEL_WIDTH$}");
+        for week in week_range.clone() {
+            let day = first_week + TimeDelta::days((week * 7 + weekday) as i64);
+            let cell = match day.year() == year && day <= today {
+                true => {
+                    let ratio = ratios.get(&day).copied().unwrap_or(0.0);
+                    cell(&cli_styles, level(ratio))
+                }
+                false => " ".to_string(),
+            };
+            line.push_str(&cell);
+            line.push_str(&" ".repeat(cell_width - 1));
+        }
+        lines.push(line.trim_end().to_string());
+    }
+
+    let legend = (0..ASCII_CELLS.len())
+        .map(|level| cell(&cli_styles, level))
+        .collect::<Vec<String>>()
+        .join("");
+    let days = ratios
+        .iter()
+        .filter(|(date, ratio)| date.year() == year && **ratio > 0.0)
+        .count();
+    lines.push(String::new());
+    let days = match days {
+        1 => "1 day".to_string(),
+        n => format!("{n} days"),
+    };
+    lines.push(format!(
+        "{}Less {legend} More    {days} with check-ins in {year}",
+        " ".repeat(LABEL_WIDTH)
+    ));
+    lines.join("\n")
+}
+
+/// Builds a month grid with the day numbers over a marker for each day
+pub fn build_month(
+    first: NaiveDate,
+    week_start: Weekday,
+    status: impl Fn(NaiveDate) -> DayStatus,
+) -> String {
+    let cli_styles = CliStyles::new();
+    let header_style = styled(Style::new().italic().fg(cli_styles.table_header_fg));
+    let mut lines = vec![
+        styled(Style::new().bold())
+            .paint(first.format("%B %Y").to_string())
+            .to_string(),
+        header_style.paint(week_header(week_start)).to_string(),
+    ];
+    for week in month_weeks(first, week_start) {
+        let mut numbers = String::new();
+        let mut markers = String::new();
+        for day in week {
+            match day {
+                Some(day) => {
+                    numbers.push_str(&format!("{:>2}  ", day.day()));
+                    markers.push_str(&format!("{}  ", status(day).marker()));
+                }
+                None => {
+                    numbers.push_str("    ");
+                    markers.push_str("    ");
+                }
+            }
+        }
+        lines.push(numbers.trim_end().to_string());
+        lines.push(markers.trim_end().to_string());
+    }
+    lines.push(String::new());
+    lines.push(
+        [
+            DayStatus::Done,
+            DayStatus::Missed,
+            DayStatus::Skipped,
+            DayStatus::Waiting,
+        ]
+        .map(|status| format!("{} {}", status.emoji(), status.as_str()))
+        .join("  "),
+    );
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(text: &str) -> String {
+        let mut plain = String::new();
+        let mut in_escape = false;
+        for c in text.chars() {
+            match (in_escape, c) {
+                (false, '\u{1b}') => in_escape = true,
+                (true, 'm') => in_escape = false,
+                (false, c) => plain.push(c),
+                _ => {}
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn heatmap_layout() {
+        let day = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        let ratios = BTreeMap::from([(day(1, 1), 1.0), (day(1, 2), 0.5)]);
+
+        let heatmap = strip(&build_heatmap(
+            2024,
+            &ratios,
+            day(12, 31),
+            200,
+            Weekday::Mon,
+        ));
+        let lines: Vec<&str> = heatmap.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert!(lines[0].starts_with("    Jan"));
+        assert_eq!(lines[1], format!("Mon {}", ["■"; 53].join(" ")));
+        assert!(lines[9].ends_with("2 days with check-ins in 2024"));
+
+        let heatmap = strip(&build_heatmap(2024, &ratios, day(3, 6), 24, Weekday::Mon));
+        let lines: Vec<&str> = heatmap.lines().collect();
+        assert_eq!(lines[3], format!("Wed {}", "■".repeat(10)));
+        assert_eq!(lines[4], format!("    {}", "■".repeat(9)));
+
+        assert_eq!([0.0, 0.2, 0.5, 0.7, 1.0].map(level), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn month_layout() {
+        let first = NaiveDate::from_ymd_opt(2024, 8, 1).unwrap();
+        let month = strip(&build_month(first, Weekday::Mon, |day| match day.day() {
+            1 => DayStatus::Done,
+            2 => DayStatus::Missed,
+            3 => DayStatus::Skipped,
+            4 => DayStatus::Waiting,
+            _ => DayStatus::Future,
+        }));
+        let lines: Vec<&str> = month.lines().collect();
+        assert_eq!(lines[0], "August 2024");
+        assert_eq!(lines[2], "             1   2   3   4");
+        assert_eq!(lines[3], "            ✅  ❌  ➖  ⏳");
+        assert_eq!(lines[4], " 5   6   7   8   9  10  11");
+        assert_eq!(lines[5], " ·   ·   ·   ·   ·   ·   ·");
+        assert_eq!(lines.len(), 14);
+    }
+}
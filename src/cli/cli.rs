@@ -1,17 +1,33 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use ansi_term::Style;
-use clap::{Parser, Subcommand};
-use console::Emoji;
+use chrono::Datelike;
+use clap::{Arg, ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+use console::Term;
 use dirs;
 use uuid::Uuid;
 
 use crate::{
-    cli::table::build_table,
-    db::Database,
+    cli::calendar::{build_heatmap, build_month},
+    cli::error::{CliError, EXIT_DATABASE, EXIT_FAILURE, EXIT_NOT_DONE, EXIT_NOT_FOUND},
+    cli::output::{render, streak_value, streak_values, OutputFormat},
+    cli::picker::Picker,
+    cli::status::{StatusCounts, DEFAULT_STATUS_FORMAT},
+    cli::table::{build_log_table, build_stats_table, build_table},
+    color::{emoji, styled, ColorChoice, Theme},
+    config::{self, Config},
+    db::{Database, MergeSummary},
+    filtering::filter_by_status,
+    export::{Export, ExportFormat},
+    import::{self, ImportSource, ImportSummary},
+    profile::{Profiles, DEFAULT_PROFILE},
+    report::{Report, ReportFormat, ReportPeriod},
     gui,
-    sorting::get_sort_order,
+    sorting::{get_sort_order, SortByDirection, SortByField},
+    stats::{month_weeks, CheckinHistory, StreakStats, Totals},
     streak::{sort_streaks, Frequency, Streak},
+    sync::{GitSync, SyncSummary},
     tui,
 };
 
@@ -20,16 +36,50 @@ use crate::{
 struct Cli {
     #[command(subcommand)]
     command: Commands,
-    #[clap(short, long, default_value = "skidmarks.ron")]
-    database_url: String,
+    #[clap(
+        short,
+        long,
+        env = "SKIDMARKS_DB",
+        help = "Database file, relative to the data directory unless absolute or starting with ~"
+    )]
+    database_url: Option<String>,
+    #[clap(
+        short,
+        long,
+        env = "SKIDMARKS_PROFILE",
+        conflicts_with = "database_url",
+        help = "Named profile to use instead of the default one"
+    )]
+    profile: Option<String>,
+    // Added to every subcommand by `command`, except those in FILE_OUTPUT
+    #[clap(
+        long,
+        value_enum,
+        help = "Print results as structured data without colors, for scripts"
+    )]
+    output: Option<OutputFormat>,
+    #[clap(
+        long,
+        global = true,
+        help = "Color theme: latte, frappe, macchiato, mocha or one from the themes directory"
+    )]
+    theme: Option<String>,
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = ColorChoice::Auto,
+        help = "Color and emoji in output: auto turns them off when piped or with NO_COLOR set"
+    )]
+    color: ColorChoice,
 }
 
 #[derive(Debug, Subcommand)]
 enum Commands {
     #[command(about = "List all streaks", long_about = None, short_flag = 'l')]
     List {
-        #[arg(long, default_value = "task+", help = "Sort by field")]
-        sort_by: String,
+        #[arg(long, help = "Sort by field, like task+ or current_streak-")]
+        sort_by: Option<String>,
 
         #[arg(long, default_value = "", help = "Search for task")]
         search: String,
@@ -48,6 +98,12 @@ enum Commands {
 
         #[arg(long, action, group = "status", help = "Show missed streaks")]
         missed: bool,
+
+        #[arg(long, help = "Show streaks with this tag")]
+        tag: Option<String>,
+
+        #[arg(long, action, help = "Show archived streaks instead")]
+        archived: bool,
     },
     #[command(about = "Create a new streak", long_about = None, short_flag = 'a')]
     Add {
@@ -58,19 +114,265 @@ enum Commands {
         task: String,
     },
     #[command(about = "Get one streak", long_about = None, short_flag='o')]
-    Get { ident: String },
-    #[command(about = "Check in to a streak", long_about = None, short_flag = 'c')]
-    CheckIn { ident: String },
-    #[command(about = "Remove a streak", long_about = None, short_flag = 'r')]
-    Remove { ident: String },
+    Get {
+        #[arg(help = "Id prefix or task name")]
+        ident: String,
+    },
+    #[command(about = "Check in to streaks, picking them from a list if none are given", long_about = None, short_flag = 'c')]
+    CheckIn {
+        #[arg(help = "Id prefixes or task names")]
+        idents: Vec<String>,
+
+        #[arg(
+            short,
+            long,
+            action,
+            conflicts_with = "idents",
+            help = "Pick from the streaks not done yet, narrowed by any selector"
+        )]
+        interactive: bool,
+
+        #[command(flatten)]
+        selector: Selector,
+    },
+    #[command(about = "Remove streaks", long_about = None, short_flag = 'r')]
+    Remove {
+        #[arg(help = "Id prefixes or task names", required_unless_present_any = SELECTORS)]
+        idents: Vec<String>,
+
+        #[command(flatten)]
+        selector: Selector,
+    },
+    #[command(about = "Add a tag to streaks, or remove it", long_about = None)]
+    Tag {
+        #[arg(long, action, help = "Remove the tag instead of adding it")]
+        remove: bool,
+
+        name: String,
+
+        #[arg(help = "Id prefixes or task names", required_unless_present_any = SELECTORS)]
+        idents: Vec<String>,
+
+        #[command(flatten)]
+        selector: Selector,
+    },
+    #[command(about = "Archive streaks, hiding them from list, today and status", long_about = None)]
+    Archive {
+        #[arg(help = "Id prefixes or task names", required_unless_present_any = SELECTORS)]
+        idents: Vec<String>,
+
+        #[command(flatten)]
+        selector: Selector,
+    },
+    #[command(about = "Bring archived streaks back", long_about = None)]
+    Unarchive {
+        #[arg(help = "Id prefixes or task names", required_unless_present_any = SELECTORS)]
+        idents: Vec<String>,
+
+        #[command(flatten)]
+        selector: Selector,
+    },
+    #[command(about = "Show what's due today, what's done and what's about to break", long_about = None)]
+    Today,
+    #[command(about = "Print a one-line summary for shell prompts and status bars", long_about = None)]
+    Status {
+        #[arg(
+            short,
+            long,
+            default_value = DEFAULT_STATUS_FORMAT,
+            help = "Template with {done}, {waiting}, {missed}, {left}, {total}, {longest} and {emoji}"
+        )]
+        format: String,
+    },
+    #[command(
+        about = "Show completion rates and other statistics",
+        long_about = "Show completion rates and other statistics. Completion rates, breaks \
+                      and weekdays need the full check-in history, which is only kept while \
+                      the journal is on (`skidmarks journal enable`). Without it, the average \
+                      streak is estimated from the counters."
+    )]
+    Stats {
+        #[arg(help = "Only show this streak, by id prefix or task name")]
+        ident: Option<String>,
+    },
+    #[command(about = "Show a year of check-ins as a heatmap", long_about = None)]
+    Calendar {
+        #[arg(help = "Only show this streak, by id prefix or task name")]
+        ident: Option<String>,
+
+        #[arg(long, help = "Year to show, this year if not given")]
+        year: Option<i32>,
+    },
+    #[command(about = "Show a month of one streak's check-ins", long_about = None)]
+    Month {
+        #[arg(help = "Id prefix or task name")]
+        ident: String,
+
+        #[arg(long, value_name = "YYYY-MM", help = "Month to show, this month if not given")]
+        month: Option<String>,
+    },
+    #[command(about = "Write a summary of a week, month or year", long_about = None)]
+    Report {
+        #[arg(short, long, value_enum, default_value_t = ReportPeriod::Week)]
+        period: ReportPeriod,
+
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Markdown)]
+        format: ReportFormat,
+
+        #[arg(
+            short,
+            long,
+            value_name = "FILE",
+            help = "File to write to instead of standard output"
+        )]
+        output: Option<String>,
+    },
+    #[command(about = "Show or change settings in the config file", long_about = None)]
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    #[command(about = "Undo the last change", long_about = None)]
+    Undo,
+    #[command(about = "Redo the last undone change", long_about = None)]
+    Redo,
+    #[command(about = "Show the history of changes", long_about = None)]
+    Log {
+        #[arg(help = "Only show changes to this streak, by id prefix or task name")]
+        ident: Option<String>,
+    },
+    #[command(about = "Turn the change journal on or off", long_about = None)]
+    Journal {
+        #[command(subcommand)]
+        command: JournalCommands,
+    },
+    #[command(about = "Manage named profiles, each with its own streaks", long_about = None)]
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommands,
+    },
+    #[command(about = "Merge in streaks from a diverged copy of the database", long_about = None)]
+    Merge { file: String },
+    #[command(about = "Sync the database through a git repository", long_about = None)]
+    Sync {
+        #[command(subcommand)]
+        command: Option<SyncCommands>,
+    },
+    #[command(about = "Export streaks and check-ins", long_about = None)]
+    Export {
+        #[arg(short, long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+
+        #[arg(
+            short,
+            long,
+            value_name = "FILE",
+            help = "File to write to instead of standard output"
+        )]
+        output: Option<String>,
+    },
+    #[command(about = "Import habits from another tracker", long_about = None)]
+    Import {
+        #[arg(
+            long,
+            value_enum,
+            help = "Where the file came from. Loop's SQLite backups and Habitica aren't supported"
+        )]
+        from: ImportSource,
+
+        #[arg(long, action, help = "Show what would be imported without saving it")]
+        dry_run: bool,
+
+        file: String,
+    },
+    #[command(about = "Encrypt the database with a passphrase", long_about = None)]
+    Encrypt,
+    #[command(about = "Store the database as plain text again", long_about = None)]
+    Decrypt,
+    #[command(about = "Show the path of the database in use", long_about = None)]
+    Where,
     #[command(about = "Switch to TUI", long_about = None)]
     Tui,
     #[command(about = "Switch to GUI", long_about = None)]
     Gui,
 }
 
+/// Ids of the `Selector` args, any of which can stand in for idents
+const SELECTORS: [&str; 3] = ["with_tag", "search", "all_waiting"];
+
+/// Picks streaks for commands that work on several at once. Every given
+/// option has to match.
+#[derive(Debug, Args)]
+struct Selector {
+    #[arg(long = "tag", value_name = "TAG", help = "Every streak with this tag")]
+    with_tag: Option<String>,
+
+    #[arg(long, value_name = "TEXT", help = "Every streak whose task contains this")]
+    search: Option<String>,
+
+    #[arg(
+        long,
+        action,
+        help = "Every streak still waiting for a check-in, unless archived"
+    )]
+    all_waiting: bool,
+}
+
+impl Selector {
+    fn is_empty(&self) -> bool {
+        self.with_tag.is_none() && self.search.is_none() && !self.all_waiting
+    }
+
+    fn matches(&self, streak: &Streak) -> bool {
+        self.with_tag.as_ref().is_none_or(|tag| streak.has_tag(tag))
+            && self
+                .search
+                .as_ref()
+                .is_none_or(|query| streak.task.contains(query.as_str()))
+            && (!self.all_waiting || (streak.is_waiting() && !streak.archived))
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommands {
+    #[command(about = "Show where the config file is", long_about = None)]
+    Path,
+    #[command(about = "Show a setting, or all of them", long_about = None)]
+    Get { key: Option<String> },
+    #[command(about = "Change a setting, or go back to the default if no value is given", long_about = None)]
+    Set { key: String, value: Option<String> },
+    #[command(about = "Open the config file in $VISUAL or $EDITOR", long_about = None)]
+    Edit,
+}
+
+#[derive(Debug, Subcommand)]
+enum JournalCommands {
+    #[command(about = "Start recording every change", long_about = None)]
+    Enable,
+    #[command(about = "Stop recording changes and discard the history", long_about = None)]
+    Disable,
+}
+
+#[derive(Debug, Subcommand)]
+enum SyncCommands {
+    #[command(about = "Set up syncing through a git repository", long_about = None)]
+    Init { repo: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum ProfileCommands {
+    #[command(about = "Create a new profile", long_about = None)]
+    Create { name: String },
+    #[command(about = "List all profiles", long_about = None)]
+    List,
+    #[command(about = "Make a profile the default", long_about = None)]
+    Switch { name: String },
+    #[command(about = "Delete a profile and its streaks", long_about = None)]
+    Delete { name: String },
+}
+
 /// Create a new daily streak item
-fn new_daily(task: String, db: &mut Database) -> Result<Streak, Box<dyn std::error::Error>> {
+fn new_daily(task: String, db: &mut Database) -> Result<Streak, std::io::Error> {
     let streak = Streak::new_daily(task);
     db.streaks.push(streak.clone());
     db.save()?;
@@ -78,7 +380,7 @@ fn new_daily(task: String, db: &mut Database) -> Result<Streak, Box<dyn std::err
 }
 
 /// Create a new weekly streak item
-fn new_weekly(task: String, db: &mut Database) -> Result<Streak, Box<dyn std::error::Error>> {
+fn new_weekly(task: String, db: &mut Database) -> Result<Streak, std::io::Error> {
     let streak = Streak::new_weekly(task);
     db.streaks.push(streak.clone());
     db.save()?;
@@ -101,66 +403,739 @@ fn get_one(db: &mut Database, id: Uuid) -> Option<Streak> {
     None
 }
 
-fn get_one_by_id(db: &mut Database, ident: &str) -> Option<Streak> {
-    if let Some(streak) = db.get_by_id(ident) {
-        return Some(streak);
+/// The one streak an ident refers to
+fn find_streak(db: &Database, ident: &str) -> Result<Streak, CliError> {
+    one_streak(db, ident, db.find_by_ident(ident))
+}
+
+/// The one streak an ident refers to by id or whole task name, for
+/// commands that destroy data
+fn find_exact_streak(db: &Database, ident: &str) -> Result<Streak, CliError> {
+    let matches = db.find_by_exact_ident(ident);
+    if matches.is_empty() {
+        if let [near] = db.find_by_ident(ident).as_slice() {
+            return Err(CliError::not_found(format!(
+                "No streak is called \"{ident}\", give the id ({}) or whole name of \"{}\" if you meant it",
+                db.short_id(near.id),
+                near.task
+            )));
+        }
+    }
+    one_streak(db, ident, matches)
+}
+
+fn one_streak(db: &Database, ident: &str, mut matches: Vec<Streak>) -> Result<Streak, CliError> {
+    match matches.len() {
+        0 => Err(CliError::not_found(format!("No streak matches \"{ident}\""))),
+        1 => Ok(matches.remove(0)),
+        n => Err(CliError::ambiguous(format!(
+            "\"{ident}\" matches {n} streaks, use more of the id: {}",
+            matches
+                .iter()
+                .map(|s| format!("{} ({})", db.short_id(s.id), s.task))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ))),
     }
-    None
 }
 
-/// Check in to a streak today
-fn checkin(db: &mut Database, ident: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let streak = db.get_by_id(ident).unwrap();
-    match db.checkin(streak.id) {
-        Ok(_) => {
-            db.save()?;
-            Ok(())
+/// The streaks named by `idents`, then those the selector matches, each
+/// once. It's an error for an ident to match no streak or several, but not
+/// for the selector to match nothing.
+fn select_streaks(
+    db: &Database,
+    idents: &[String],
+    selector: &Selector,
+    find: fn(&Database, &str) -> Result<Streak, CliError>,
+) -> Result<Vec<Streak>, CliError> {
+    let mut streaks: Vec<Streak> = vec![];
+    for ident in idents {
+        let streak = find(db, ident)?;
+        if !streaks.iter().any(|s| s.id == streak.id) {
+            streaks.push(streak);
+        }
+    }
+    if !selector.is_empty() {
+        for streak in db.streaks.iter().filter(|s| selector.matches(s)) {
+            if !streaks.iter().any(|s| s.id == streak.id) {
+                streaks.push(streak.clone());
+            }
         }
-        Err(e) => Err(Box::new(e)),
     }
+    Ok(streaks)
 }
 
-/// Remove a streak
-fn delete(db: &mut Database, ident: &str) -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(streak) = get_one_by_id(db, ident) {
-        db.delete(streak.id)?;
-        db.save()?;
+/// Let the user tick streaks that aren't done yet from a list
+fn pick_streaks(db: &Database, selector: &Selector) -> Result<Vec<Streak>, CliError> {
+    let term = Term::stderr();
+    if !term.is_term() {
+        return Err(CliError::new(
+            EXIT_FAILURE,
+            "Error checking in:",
+            "Give idents or a selector, or run in a terminal to pick streaks from a list",
+        ));
+    }
+    let mut candidates: Vec<Streak> = db
+        .streaks
+        .iter()
+        .filter(|s| !s.is_done() && !s.archived && (selector.is_empty() || selector.matches(s)))
+        .cloned()
+        .collect();
+    candidates.sort_by_key(|s| s.task.to_lowercase());
+    if candidates.is_empty() {
+        return Ok(vec![]);
+    }
+    let items = candidates
+        .iter()
+        .map(|s| format!("{} ({}, {})", s.task, s.frequency, s.status()))
+        .collect();
+    let picked = Picker::new(items)
+        .interact(&term, "Check in to:")
+        .map_err(|e| CliError::io("Error checking in:", e))?;
+    Ok(picked.into_iter().map(|i| candidates[i].clone()).collect())
+}
+
+/// Check in to streaks today with a single save. Each streak comes back as
+/// it is now, with whether it was checked in or already had been today.
+fn checkin(db: &mut Database, streaks: &[Streak]) -> Result<Vec<(Streak, bool)>, CliError> {
+    let mut results = vec![];
+    for streak in streaks {
+        db.checkin(streak.id)
+            .map_err(|e| CliError::io("Error checking in:", e))?;
+        let updated = db.get_one(streak.id).unwrap_or_else(|| streak.clone());
+        let checked_in = updated.total_checkins != streak.total_checkins;
+        results.push((updated, checked_in));
+    }
+    db.save()
+        .map_err(|e| CliError::io("Error checking in:", e))?;
+    Ok(results)
+}
+
+/// Remove streaks with a single save
+fn delete(db: &mut Database, streaks: &[Streak]) -> Result<(), CliError> {
+    for streak in streaks {
+        db.delete(streak.id)
+            .map_err(|e| CliError::io("Error removing streak:", e))?;
+    }
+    db.save()
+        .map_err(|e| CliError::io("Error removing streak:", e))
+}
+
+/// Add a tag to streaks, or take it off, with a single save. Each streak
+/// comes back with whether its tags changed.
+fn tag(
+    db: &mut Database,
+    name: &str,
+    remove: bool,
+    streaks: &[Streak],
+) -> Result<Vec<(Streak, bool)>, CliError> {
+    let name = name.trim();
+    if name.is_empty() || name.contains(';') {
+        return Err(CliError::new(
+            EXIT_FAILURE,
+            "Error tagging streaks:",
+            "Tags can't be empty or contain \";\"",
+        ));
+    }
+    let mut results = vec![];
+    for streak in streaks {
+        if let Some(found) = db.streaks.iter_mut().find(|s| s.id == streak.id) {
+            let changed = match remove {
+                true => found.remove_tag(name),
+                false => found.add_tag(name),
+            };
+            results.push((found.clone(), changed));
+        }
+    }
+    db.save()
+        .map_err(|e| CliError::io("Error tagging streaks:", e))?;
+    Ok(results)
+}
+
+/// Archive streaks, or bring them back, with a single save. Each streak
+/// comes back with whether it changed.
+fn archive(
+    db: &mut Database,
+    archived: bool,
+    streaks: &[Streak],
+) -> Result<Vec<(Streak, bool)>, CliError> {
+    let mut results = vec![];
+    for streak in streaks {
+        if let Some(found) = db.streaks.iter_mut().find(|s| s.id == streak.id) {
+            let changed = found.set_archived(archived);
+            results.push((found.clone(), changed));
+        }
+    }
+    db.save()
+        .map_err(|e| CliError::io("Error archiving streaks:", e))?;
+    Ok(results)
+}
+
+/// Print one line per streak a batch command touched, and a count when
+/// there was more than one
+fn print_batch(
+    response_style: Style,
+    results: &[(Streak, bool)],
+    changed: (&str, &str),
+    unchanged: &str,
+) {
+    let (emoji, message) = changed;
+    if results.is_empty() {
+        println!("{}", response_style.paint("Nothing changed"));
+    }
+    for (streak, was_changed) in results {
+        match was_changed {
+            true => println!("{emoji} {} {}", response_style.paint(message), streak.task),
+            false => println!("   {unchanged} {}", streak.task),
+        }
+    }
+    if results.len() > 1 {
+        let count = results.iter().filter(|(_, changed)| *changed).count();
+        println!("{count} of {} streaks changed", results.len());
+    }
+}
+
+/// Each streak a batch command touched, with the action taken on it
+fn batch_value(results: &[(Streak, bool)], changed: &str, unchanged: &str) -> Value {
+    Value::Array(
+        results
+            .iter()
+            .map(|(streak, was_changed)| match was_changed {
+                true => streak_value(Some(changed), streak),
+                false => streak_value(Some(unchanged), streak),
+            })
+            .collect(),
+    )
+}
+
+/// The profile in use, unless a database file was picked some other way
+pub fn get_profile(database_url: &Option<String>, profile: Option<String>) -> Option<String> {
+    if database_url.is_some() {
+        return None;
+    }
+    let config = Config::load();
+    match (profile, config.database) {
+        (Some(profile), _) => Some(profile),
+        (None, Some(_)) => None,
+        (None, None) => Some(
+            config
+                .default_profile
+                .unwrap_or_else(|| DEFAULT_PROFILE.to_string()),
+        ),
     }
-    Ok(())
 }
 
-pub fn get_database_url() -> String {
-    let cli = Cli::parse();
-    let path = Path::new(&dirs::data_local_dir().unwrap()).join(cli.database_url);
+/// Work out which database file to use: the `--database-url` flag or
+/// `SKIDMARKS_DB`, then the `--profile` flag, then the config file, then the
+/// default profile. Relative paths live in the platform data directory.
+pub fn get_database_url(database_url: Option<String>, profile: Option<String>) -> String {
+    let name = match get_profile(&database_url, profile) {
+        Some(profile) => Profiles::new().path(&profile).to_string_lossy().to_string(),
+        None => database_url
+            .or_else(|| Config::load().database)
+            .unwrap_or_else(|| "skidmarks.ron".to_string()),
+    };
+    let path = match name.strip_prefix('~') {
+        Some("") => dirs::home_dir().unwrap_or_default(),
+        Some(rest) if rest.starts_with(['/', '\\']) => {
+            dirs::home_dir().unwrap_or_default().join(&rest[1..])
+        }
+        _ => PathBuf::from(&name),
+    };
+    let path = match path.is_absolute() {
+        true => path,
+        false => Path::new(&dirs::data_local_dir().unwrap()).join(path),
+    };
     path.to_string_lossy().to_string()
 }
 
-/// Parses command line options
+/// Read a passphrase from `SKIDMARKS_PASSPHRASE` or ask for it without echoing
+fn get_passphrase(prompt: &str, confirm: bool) -> Result<String, std::io::Error> {
+    if let Ok(passphrase) = std::env::var("SKIDMARKS_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+    let term = Term::stderr();
+    term.write_str(prompt)?;
+    let passphrase = term.read_secure_line()?;
+    if confirm {
+        term.write_str("Repeat passphrase: ")?;
+        if term.read_secure_line()? != passphrase {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Passphrases don't match",
+            ));
+        }
+    }
+    match passphrase.is_empty() {
+        true => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "The passphrase can't be empty",
+        )),
+        false => Ok(passphrase),
+    }
+}
+
+/// Merge another database file into this one and save the result
+fn merge_file(db: &mut Database, file: &str) -> Result<MergeSummary, std::io::Error> {
+    if !Path::new(file).exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{file} not found"),
+        ));
+    }
+    let other = match Database::is_encrypted(file) && !db.encrypted() {
+        true => Database::open(file, Some(&get_passphrase(&format!("Passphrase for {file}: "), false)?))?,
+        false => db.reopen(file)?,
+    };
+    let history = CheckinHistory::load(&other)?;
+    let summary = db.merge_streaks(other.streaks, history.as_ref())?;
+    db.save()?;
+    Ok(summary)
+}
+
+/// Run `sync`, or set it up with `sync init`
+fn sync_command(
+    db: &mut Database,
+    command: &Option<SyncCommands>,
+) -> Result<(GitSync, Option<SyncSummary>), std::io::Error> {
+    match command {
+        Some(SyncCommands::Init { repo }) => {
+            Ok((GitSync::init(&db.filename, Path::new(repo))?, None))
+        }
+        None => match GitSync::open(&db.filename) {
+            Some(sync) => {
+                let summary = sync.sync(db)?;
+                Ok((sync, Some(summary)))
+            }
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Sync isn't set up, run `skidmarks sync init <repo>` first",
+            )),
+        },
+    }
+}
+
+/// Import habits from a file, saving them unless it is a dry run
+fn import_file(
+    db: &mut Database,
+    from: ImportSource,
+    file: &str,
+    dry_run: bool,
+) -> Result<ImportSummary, std::io::Error> {
+    let habits = import::read(from, Path::new(file), config::today())?;
+    let summary = import::plan(db, habits);
+    if !dry_run {
+        for streak in &summary.created {
+            db.add(streak.clone())?;
+            // Check-in history goes in the journal, so stats and calendars show it
+            db.backfill(streak.id, &summary.dates[&streak.id]);
+        }
+        db.save()?;
+    }
+    Ok(summary)
+}
+
+/// Print a command's result as structured data if `--output` was given, or
+/// run `human` to print it for people
+fn emit(output: Option<OutputFormat>, value: Value, human: impl FnOnce()) {
+    match output {
+        Some(format) => println!("{}", render(format, &value)),
+        None => human(),
+    }
+}
+
+fn merge_value(action: &str, summary: &MergeSummary) -> Value {
+    json!({
+        "action": action,
+        "added": streak_values(&summary.added),
+        "updated": streak_values(&summary.updated),
+        "unchanged": summary.unchanged,
+        "conflicts": streak_values(&summary.conflicts),
+    })
+}
+
+/// Split streaks into those at risk of breaking, those still to do and those done
+fn today_groups(streaks: Vec<Streak>) -> [(&'static str, &'static str, Vec<Streak>); 3] {
+    let (done, due): (Vec<Streak>, Vec<Streak>) = streaks.into_iter().partition(|s| s.is_done());
+    let (at_risk, to_do) = due.into_iter().partition(|s| s.is_at_risk());
+    [
+        ("at_risk", "At risk of breaking", at_risk),
+        ("to_do", "Still to do", to_do),
+        ("done", "Done", done),
+    ]
+}
+
+/// Print the streaks a merge added or updated
+fn print_merge_summary(summary: &MergeSummary) {
+    for streak in &summary.added {
+        println!("  added: {}", streak.task);
+    }
+    for streak in &summary.updated {
+        println!("  updated: {}", streak.task);
+    }
+    for streak in &summary.conflicts {
+        println!(
+            "  conflict: {} was checked in on in both copies, kept the higher counts",
+            streak.task
+        );
+    }
+}
+
+/// Run a profile subcommand
+fn profile_command(
+    command: &ProfileCommands,
+    active: Option<String>,
+) -> Result<String, std::io::Error> {
+    let profiles = Profiles::new();
+    match command {
+        ProfileCommands::Create { name } => {
+            profiles.create(name)?;
+            Ok(format!("Created profile: {name}"))
+        }
+        ProfileCommands::List => {
+            let lines = profiles
+                .list()
+                .into_iter()
+                .map(|name| match Some(&name) == active.as_ref() {
+                    true => format!("* {name}"),
+                    false => format!("  {name}"),
+                })
+                .collect::<Vec<String>>();
+            Ok(lines.join("\n"))
+        }
+        ProfileCommands::Switch { name } => {
+            if !profiles.exists(name) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("Profile \"{name}\" not found"),
+                ));
+            }
+            let mut config = Config::load();
+            config.default_profile = Some(name.clone());
+            config.save()?;
+            Ok(format!("Switched to profile: {name}"))
+        }
+        ProfileCommands::Delete { name } => {
+            profiles.delete(name)?;
+            let mut config = Config::load();
+            if config.default_profile.as_ref() == Some(name) {
+                config.default_profile = None;
+                config.save()?;
+            }
+            Ok(format!("Deleted profile: {name}"))
+        }
+    }
+}
+
+/// Parses command line options, exiting with one of the codes in
+/// `cli::error` if the command fails
 pub fn parse() {
+    // clap exits with 2 on usage errors, which is taken by EXIT_NOT_FOUND here
+    let cli = command()
+        .try_get_matches()
+        .and_then(|matches| {
+            let mut cli = Cli::from_arg_matches(&matches)?;
+            cli.output = cli.output.or_else(|| subcommand_format(&matches));
+            Ok(cli)
+        })
+        .unwrap_or_else(|err| {
+            let _ = err.print();
+            std::process::exit(match err.use_stderr() {
+                true => EXIT_FAILURE,
+                false => 0,
+            })
+        });
+    // Picked before any styles are built, since they all use the current theme
+    let picked = match &cli.theme {
+        Some(theme) => Theme::select(theme)
+            .map_err(|e| CliError::new(EXIT_FAILURE, "Error picking theme:", e)),
+        None => Ok(()),
+    };
+    // The TUI and GUI draw their own colors, whatever stdout is
+    if !matches!(cli.command, Commands::Tui | Commands::Gui) {
+        cli.color.apply();
+    }
     let cli_styles = crate::color::CliStyles::new();
-    let cli = Cli::parse();
-    let db_url = get_database_url();
-    let mut db = Database::new(&db_url).expect("Could not load database");
-    let response_style = Style::new().bold().fg(cli_styles.response_fg.into());
-    match &cli.command {
-        Commands::Add { task, frequency } => match frequency {
-            Frequency::Daily => {
-                let streak = new_daily(task.to_string(), &mut db).unwrap();
-                let response = response_style
-                    .paint("Created a new daily streak:")
-                    .to_string();
-                let tada = Emoji("🎉", "");
-                println!("{tada} {response} {}", streak.task);
+    let error_style = match output_format(&cli) {
+        Some(_) => Style::new(),
+        None => styled(Style::new().bold().fg(cli_styles.response_error_fg)),
+    };
+    match picked.and_then(|_| run(&cli)) {
+        Ok(0) => {}
+        Ok(code) => std::process::exit(code),
+        Err(err) => {
+            match output_format(&cli) {
+                Some(format) => {
+                    let value =
+                        json!({"error": err.message, "context": err.context, "code": err.code});
+                    eprintln!("{}", render(format, &value));
+                }
+                None => eprintln!("{} {}", error_style.paint(&err.context), err.message),
             }
-            Frequency::Weekly => {
-                let streak = new_weekly(task.to_string(), &mut db).unwrap();
-                let response = response_style
-                    .paint("Created a new weekly streak:")
-                    .to_string();
-                let tada = Emoji("🎉", "");
-                println!("{tada} {response} {}", streak.task);
+            std::process::exit(err.code);
+        }
+    }
+}
+
+/// Commands where `--output` is the file to write rather than a format
+const FILE_OUTPUT: [&str; 2] = ["export", "report"];
+
+/// The command line, with the `--output` format flag on every subcommand
+/// except those in `FILE_OUTPUT`. A global flag would clash with theirs.
+fn command() -> clap::Command {
+    let command = Cli::command();
+    let format = command
+        .get_arguments()
+        .find(|arg| arg.get_id() == "output")
+        .cloned()
+        .expect("Cli has an --output flag");
+    with_format(command, &format)
+}
+
+fn with_format(mut command: clap::Command, format: &Arg) -> clap::Command {
+    let names: Vec<String> = command
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_string())
+        .filter(|name| !FILE_OUTPUT.contains(&name.as_str()))
+        .collect();
+    for name in names {
+        command = command.mut_subcommand(name, |sub| with_format(sub.arg(format.clone()), format));
+    }
+    command
+}
+
+/// An `--output` format given after the subcommand
+fn subcommand_format(matches: &ArgMatches) -> Option<OutputFormat> {
+    let (_, sub) = matches.subcommand()?;
+    match sub.try_get_one::<OutputFormat>("output") {
+        Ok(Some(format)) => Some(*format),
+        _ => subcommand_format(sub),
+    }
+}
+
+/// The `--output` flag, or the config file's output format if it wasn't given
+fn output_format(cli: &Cli) -> Option<OutputFormat> {
+    cli.output.or_else(|| {
+        let output = Config::current().output.as_deref()?;
+        OutputFormat::from_str(output, true).ok()
+    })
+}
+
+/// Show or change the config file. Changes take effect from the next run.
+fn config_command(
+    command: &ConfigCommands,
+    output: Option<OutputFormat>,
+    response_style: Style,
+) -> Result<i32, CliError> {
+    let path = Config::path();
+    match command {
+        ConfigCommands::Path => emit(output, json!({"path": path}), || {
+            println!("{}", path.display())
+        }),
+        ConfigCommands::Get { key } => {
+            let config = Config::load();
+            let keys = match key {
+                Some(key) => vec![key.as_str()],
+                None => config::KEYS.to_vec(),
+            };
+            let mut values = serde_json::Map::new();
+            for key in keys {
+                let value = config
+                    .get(key)
+                    .map_err(|e| CliError::new(EXIT_FAILURE, "Error reading config:", e))?;
+                values.insert(key.to_string(), json!(value));
             }
-        },
+            emit(output, Value::Object(values.clone()), || match key {
+                Some(key) => println!("{}", values[key].as_str().unwrap_or_default()),
+                None => {
+                    for (key, value) in &values {
+                        println!("{key} = {}", value.as_str().unwrap_or_default());
+                    }
+                }
+            });
+        }
+        ConfigCommands::Set { key, value } => {
+            let mut config = Config::load();
+            let valid_output = match (key.as_str(), value) {
+                ("output", Some(value)) => OutputFormat::from_str(value, true)
+                    .map(|_| ())
+                    .map_err(|_| {
+                        format!("\"{value}\" isn't an output format, expected json, ndjson, tsv or plain")
+                    }),
+                _ => Ok(()),
+            };
+            valid_output
+                .and_then(|_| config.set(key, value.as_deref()))
+                .map_err(|e| CliError::new(EXIT_FAILURE, "Error changing config:", e))?;
+            config
+                .save()
+                .map_err(|e| CliError::io("Error saving config:", e))?;
+            let value = config.get(key).ok().flatten();
+            emit(
+                output,
+                json!({"action": "set", "key": key, "value": value}),
+                || {
+                    let gear = emoji("⚙️", "*");
+                    match value {
+                        Some(value) => {
+                            let response = response_style.paint(format!("Set {key} to"));
+                            println!("{gear} {response} {value}");
+                        }
+                        None => {
+                            let response = response_style.paint(format!("Reset {key}"));
+                            println!("{gear} {response}");
+                        }
+                    }
+                },
+            );
+        }
+        ConfigCommands::Edit => {
+            if !path.exists() {
+                Config::load()
+                    .save()
+                    .map_err(|e| CliError::io("Error saving config:", e))?;
+            }
+            let editor = std::env::var("VISUAL")
+                .or_else(|_| std::env::var("EDITOR"))
+                .unwrap_or_else(|_| "vi".to_string());
+            // Editors are often given with arguments, like `code --wait`
+            let mut words = editor.split_whitespace();
+            let program = words.next().unwrap_or("vi");
+            let status = std::process::Command::new(program)
+                .args(words)
+                .arg(&path)
+                .status()
+                .map_err(|e| CliError::io("Error opening editor:", e))?;
+            if !status.success() {
+                return Err(CliError::new(
+                    EXIT_FAILURE,
+                    "Error editing config:",
+                    format!("{program} exited with {status}"),
+                ));
+            }
+            let contents = std::fs::read_to_string(&path)
+                .map_err(|e| CliError::io("Error reading config:", e))?;
+            if let Err(e) = ron::de::from_str::<Config>(&contents) {
+                return Err(CliError::new(
+                    EXIT_FAILURE,
+                    "The config file is invalid and will be ignored:",
+                    e,
+                ));
+            }
+        }
+    }
+    Ok(0)
+}
+
+/// Print counts for a prompt or status bar, returning `EXIT_NOT_DONE` if any
+/// streak is still to do. It never prompts, creates the database or saves it.
+fn status(db_url: &str, format: &str, output: Option<OutputFormat>) -> Result<i32, CliError> {
+    let passphrase = std::env::var("SKIDMARKS_PASSPHRASE").ok();
+    if Database::is_encrypted(db_url) && passphrase.is_none() {
+        return Err(CliError::new(
+            EXIT_DATABASE,
+            "Could not load database:",
+            "Set SKIDMARKS_PASSPHRASE to read an encrypted database without a prompt",
+        ));
+    }
+    let streaks = match Path::new(db_url).exists() {
+        true => {
+            Database::open(db_url, passphrase.as_deref())
+                .map_err(|e| CliError::new(EXIT_DATABASE, "Could not load database:", e))?
+                .streaks
+        }
+        false => vec![],
+    };
+    let streaks: Vec<Streak> = streaks.into_iter().filter(|s| !s.archived).collect();
+    let counts = StatusCounts::new(&streaks);
+    let line = counts
+        .render(format)
+        .map_err(|e| CliError::new(EXIT_FAILURE, "Invalid format:", e))?;
+    let mut value = json!(counts);
+    value["left"] = json!(counts.left());
+    value["emoji"] = json!(counts.emoji());
+    emit(output, value, || println!("{line}"));
+    Ok(match counts.left() {
+        0 => 0,
+        _ => EXIT_NOT_DONE,
+    })
+}
+
+/// The journal's check-in history, which some commands can't do without
+fn require_history(db: &Database) -> Result<CheckinHistory, CliError> {
+    match CheckinHistory::load(db) {
+        Ok(Some(history)) => Ok(history),
+        Ok(None) => Err(CliError::new(
+            EXIT_FAILURE,
+            "The journal is off.",
+            "Turn it on with `skidmarks journal enable`.",
+        )),
+        Err(e) => Err(CliError::io("Error reading journal:", e)),
+    }
+}
+
+/// The first day of a month given as `YYYY-MM`
+fn parse_month(month: &str) -> Result<chrono::NaiveDate, CliError> {
+    chrono::NaiveDate::parse_from_str(&format!("{}-01", month.trim()), "%Y-%m-%d").map_err(|_| {
+        CliError::new(
+            EXIT_FAILURE,
+            "Invalid month:",
+            format!("\"{month}\" isn't a month like 2026-10"),
+        )
+    })
+}
+
+/// Run a parsed command, returning the code to exit with
+fn run(cli: &Cli) -> Result<i32, CliError> {
+    let cli_styles = crate::color::CliStyles::new();
+    let output = output_format(cli);
+    let response_style = match output {
+        Some(_) => Style::new(),
+        None => styled(Style::new().bold().fg(cli_styles.response_fg)),
+    };
+    if let Commands::Config { command } = &cli.command {
+        return config_command(command, output, response_style);
+    }
+    let profile = get_profile(&cli.database_url, cli.profile.clone());
+    let db_url = get_database_url(cli.database_url.clone(), cli.profile.clone());
+    if let Some(name) = &profile {
+        if !Profiles::new().exists(name) && !matches!(cli.command, Commands::Profile { .. }) {
+            return Err(CliError::not_found(format!(
+                "Profile \"{name}\" doesn't exist. Create it with `skidmarks profile create {name}`."
+            )));
+        }
+    }
+    if let Commands::Status { format } = &cli.command {
+        return status(&db_url, format, output);
+    }
+    // Printing the path shouldn't create the file or ask for a passphrase
+    if let Commands::Where = &cli.command {
+        emit(output, json!({"database": db_url}), || println!("{db_url}"));
+        return Ok(0);
+    }
+    let opened = match Database::is_encrypted(&db_url) {
+        true => get_passphrase("Passphrase: ", false)
+            .and_then(|passphrase| Database::open(&db_url, Some(&passphrase))),
+        false => Database::new(&db_url),
+    };
+    let mut db = opened.map_err(|e| CliError::new(EXIT_DATABASE, "Could not load database:", e))?;
+    match &cli.command {
+        Commands::Add { task, frequency } => {
+            let result = match frequency {
+                Frequency::Daily => new_daily(task.to_string(), &mut db),
+                Frequency::Weekly => new_weekly(task.to_string(), &mut db),
+            };
+            match result {
+                Ok(streak) => emit(output, streak_value(Some("added"), &streak), || {
+                    let response = response_style
+                        .paint(format!("Created a new {frequency} streak:"))
+                        .to_string();
+                    let tada = emoji("🎉", "+");
+                    println!("{tada} {response} {}", streak.task);
+                }),
+                Err(e) => return Err(CliError::io("Error creating streak:", e)),
+            }
+        }
         Commands::List {
             sort_by,
             search,
@@ -169,12 +1144,18 @@ pub fn parse() {
             done,
             waiting,
             missed,
+            tag,
+            archived,
         } => {
             let mut streak_list = match search.is_empty() {
                 true => db.get_all(),
                 false => db.search(search),
             };
-            let sort_by = get_sort_order(sort_by);
+            let config = Config::current();
+            let sort_by = match sort_by {
+                Some(sort_by) => get_sort_order(sort_by),
+                None => config.sort_order(),
+            };
 
             if *daily {
                 streak_list = streak_list
@@ -202,39 +1183,476 @@ pub fn parse() {
                 streak_list = streak_list.into_iter().filter(|s| s.is_waiting()).collect();
             }
 
+            if !(*done || *missed || *waiting) {
+                streak_list = filter_by_status(streak_list, config.filter());
+            }
+
+            if let Some(tag) = tag {
+                streak_list.retain(|s| s.has_tag(tag));
+            }
+
+            streak_list.retain(|s| s.archived == *archived);
+
             streak_list = sort_streaks(streak_list, sort_by.0, sort_by.1);
-            println!("{}", build_table(streak_list));
+            emit(output, streak_values(&streak_list), || {
+                println!("{}", build_table(streak_list, &db))
+            });
         }
         Commands::Get { ident } => {
-            let streak = vec![db.get_by_id(&ident).unwrap()];
-            println!("{}", build_table(streak));
-        }
-        Commands::CheckIn { ident } => match checkin(&mut db, ident) {
-            Ok(_) => {
-                let streak = db.get_by_id(&ident).unwrap();
-                let response = response_style.paint("Checked in on").to_string();
-                let star = Emoji("🌟", "");
-                println!("{star} {response}: {}", streak.task);
+            let streak = find_streak(&db, ident)?;
+            emit(output, streak_value(None, &streak), || {
+                println!("{}", build_table(vec![streak], &db))
+            });
+        }
+        Commands::CheckIn {
+            idents,
+            interactive,
+            selector,
+        } => {
+            let streaks = match *interactive || (idents.is_empty() && selector.is_empty()) {
+                true => pick_streaks(&db, selector)?,
+                false => select_streaks(&db, idents, selector, find_streak)?,
+            };
+            let results = checkin(&mut db, &streaks)?;
+            let value = batch_value(&results, "checked_in", "already_checked_in");
+            emit(output, value, || {
+                let star = emoji("🌟", "*").to_string();
+                let changed = (star.as_str(), "Checked in on:");
+                print_batch(response_style, &results, changed, "Already checked in:");
+            });
+        }
+        Commands::Remove { idents, selector } => {
+            let streaks = select_streaks(&db, idents, selector, find_exact_streak)?;
+            delete(&mut db, &streaks)?;
+            let results: Vec<(Streak, bool)> = streaks.into_iter().map(|s| (s, true)).collect();
+            emit(output, batch_value(&results, "removed", "removed"), || {
+                let trash = emoji("🗑️", "-").to_string();
+                print_batch(response_style, &results, (trash.as_str(), "Removed:"), "");
+            });
+        }
+        Commands::Tag {
+            remove,
+            name,
+            idents,
+            selector,
+        } => {
+            let streaks = select_streaks(&db, idents, selector, find_streak)?;
+            let results = tag(&mut db, name, *remove, &streaks)?;
+            let (action, message, unchanged) = match remove {
+                true => ("untagged", format!("Untagged {name}:"), "Wasn't tagged:"),
+                false => ("tagged", format!("Tagged {name}:"), "Already tagged:"),
+            };
+            emit(output, batch_value(&results, action, "unchanged"), || {
+                let label = emoji("🏷️", "#").to_string();
+                print_batch(response_style, &results, (label.as_str(), &message), unchanged);
+            });
+        }
+        Commands::Archive { idents, selector } | Commands::Unarchive { idents, selector } => {
+            let archived = matches!(cli.command, Commands::Archive { .. });
+            let streaks = select_streaks(&db, idents, selector, find_streak)?;
+            let results = archive(&mut db, archived, &streaks)?;
+            let (action, message, unchanged) = match archived {
+                true => ("archived", "Archived:", "Already archived:"),
+                false => ("unarchived", "Unarchived:", "Wasn't archived:"),
+            };
+            emit(output, batch_value(&results, action, "unchanged"), || {
+                let label = emoji("📦", "~").to_string();
+                print_batch(
+                    response_style,
+                    &results,
+                    (label.as_str(), message),
+                    unchanged,
+                );
+            });
+        }
+        // Handled before the database is opened
+        Commands::Status { .. } | Commands::Config { .. } | Commands::Where => unreachable!(),
+        Commands::Today => {
+            let mut streaks =
+                sort_streaks(db.get_all(), SortByField::Task, SortByDirection::Ascending);
+            streaks.retain(|s| !s.archived);
+            let total = streaks.len();
+            let groups = today_groups(streaks);
+            let done = groups[2].2.len();
+            let value = groups
+                .iter()
+                .flat_map(|(group, _, streaks)| {
+                    streaks.iter().map(move |streak| {
+                        let mut value = streak_value(None, streak);
+                        value["group"] = json!(group);
+                        value
+                    })
+                })
+                .collect();
+            emit(output, Value::Array(value), || {
+                let calendar = emoji("📅", "=");
+                let summary = response_style.paint(format!("{done}/{total} done"));
+                println!("{calendar} {summary}");
+                for (_, heading, streaks) in groups {
+                    if !streaks.is_empty() {
+                        println!("\n{}", response_style.paint(heading));
+                        println!("{}", build_table(streaks, &db));
+                    }
+                }
+            });
+        }
+        Commands::Stats { ident } => {
+            let streaks = match ident {
+                Some(ident) => vec![find_streak(&db, ident)?],
+                None => sort_streaks(db.get_all(), SortByField::Task, SortByDirection::Ascending),
+            };
+            let history =
+                CheckinHistory::load(&db).map_err(|e| CliError::io("Error reading journal:", e))?;
+            let today = config::today();
+            let stats: Vec<StreakStats> = streaks
+                .iter()
+                .map(|streak| StreakStats::new(streak, history.as_ref(), today))
+                .collect();
+            let totals = ident.is_none().then(|| Totals::new(&stats));
+            let value = match &totals {
+                Some(totals) => json!({"streaks": stats, "totals": totals}),
+                None => json!(stats[0]),
+            };
+            emit(output, value, || {
+                println!("{}", build_stats_table(&stats, totals.as_ref(), &db));
+                if history.is_none() {
+                    println!(
+                        "The average streak is estimated without the journal. Turn it on with `skidmarks journal enable` to keep the full history for completion rates, breaks and weekdays."
+                    );
+                }
+            });
+        }
+        Commands::Calendar { ident, year } => {
+            let streaks = match ident {
+                Some(ident) => vec![find_streak(&db, ident)?],
+                None => db.get_all(),
+            };
+            let history = require_history(&db)?;
+            let today = config::today();
+            let year = year.unwrap_or(today.year());
+            let ratios = history.daily_ratios(&streaks);
+            let value = ratios
+                .iter()
+                .filter(|(date, _)| date.year() == year)
+                .map(|(date, ratio)| json!({"date": date, "ratio": ratio}))
+                .collect();
+            emit(output, Value::Array(value), || {
+                let width = term_size::dimensions().map_or(60, |(w, _)| w);
+                if let [streak] = streaks.as_slice() {
+                    println!("{}\n", response_style.paint(&streak.task));
+                }
+                let week_start = Config::current().week_start();
+                println!("{}", build_heatmap(year, &ratios, today, width, week_start));
+            });
+        }
+        Commands::Report {
+            period,
+            format,
+            output: file,
+        } => {
+            let history = require_history(&db)?;
+            let today = config::today();
+            let data = Report::new(&db.get_all(), &history, *period, today).render(*format);
+            match file {
+                Some(file) => match std::fs::write(file, data) {
+                    Ok(_) => emit(output, json!({"action": "reported", "file": file}), || {
+                        let response = response_style.paint("Report written to").to_string();
+                        let memo = emoji("📝", ">");
+                        println!("{memo} {response} {file}");
+                    }),
+                    Err(e) => return Err(CliError::io("Error writing report:", e)),
+                },
+                None => emit(output, json!({"report": data}), || println!("{data}")),
             }
-            Err(e) => {
-                let response = Style::new()
-                    .bold()
-                    .fg(cli_styles.response_error_fg)
-                    .paint("Error checking in:");
-                eprintln!("{response} {}", e)
+        }
+        Commands::Month { ident, month } => {
+            let streak = find_streak(&db, ident)?;
+            let history = require_history(&db)?;
+            let today = config::today();
+            let first = match month {
+                Some(month) => parse_month(month)?,
+                None => today.with_day(1).unwrap_or(today),
+            };
+            let week_start = Config::current().week_start();
+            let status = |day| history.day_status(&streak, day, today);
+            let value = month_weeks(first, week_start)
+                .iter()
+                .flatten()
+                .flatten()
+                .map(|day| json!({"date": day, "status": status(*day).as_str()}))
+                .collect();
+            emit(output, Value::Array(value), || {
+                println!("{}\n", response_style.paint(&streak.task));
+                println!("{}", build_month(first, week_start, status));
+            });
+        }
+        Commands::Undo | Commands::Redo => {
+            let (result, action, done, nothing) = match &cli.command {
+                Commands::Undo => (db.undo(), "undo", "Undid:", "Nothing to undo"),
+                _ => (db.redo(), "redo", "Redid:", "Nothing to redo"),
+            };
+            match result {
+                Ok(Some(change)) => {
+                    let value = json!({"action": action, "change": change.to_string()});
+                    emit(output, value, || {
+                        let response = response_style.paint(done).to_string();
+                        let arrow = emoji("↩️", "<");
+                        println!("{arrow} {response} {change}");
+                    });
+                }
+                Ok(None) => emit(output, json!({"action": action, "change": null}), || {
+                    println!("{}", response_style.paint(nothing))
+                }),
+                Err(e) => return Err(CliError::io("Error updating streaks:", e)),
+            }
+        }
+        Commands::Log { ident } => match db.journal() {
+            Some(journal) => match journal.events() {
+                Ok(mut events) => {
+                    if let Some(ident) = ident {
+                        // Removed streaks can only be found by id in the journal itself
+                        match find_streak(&db, ident) {
+                            Ok(streak) => events.retain(|e| e.action.streak().id == streak.id),
+                            Err(err) if err.code == EXIT_NOT_FOUND => events
+                                .retain(|e| e.action.streak().id.to_string().starts_with(ident)),
+                            Err(err) => return Err(err),
+                        }
+                    }
+                    let value = events
+                        .iter()
+                        .map(|event| {
+                            json!({
+                                "timestamp": event.timestamp,
+                                "action": event.action.to_string(),
+                                "id": event.action.streak().id,
+                                "task": event.action.streak().task,
+                            })
+                        })
+                        .collect();
+                    emit(output, Value::Array(value), || {
+                        println!("{}", build_log_table(events, &db))
+                    });
+                }
+                Err(e) => return Err(CliError::io("Error reading journal:", e)),
+            },
+            None => {
+                return Err(CliError::new(
+                    EXIT_FAILURE,
+                    "The journal is off.",
+                    "Turn it on with `skidmarks journal enable`.",
+                ))
             }
         },
-        Commands::Remove { ident } => {
-            let streak = db.get_by_id(&ident).unwrap();
-            let _ = delete(&mut db, &ident);
-            let name = &streak.task;
-            let response = response_style.paint("Removed:").to_string();
-            let trash = Emoji("🗑️", "");
-            println!("{trash} {response} {}", name);
+        Commands::Journal { command } => {
+            let (result, action, message) = match command {
+                JournalCommands::Enable => (
+                    db.enable_journal().map(|_| ()),
+                    "journal_enabled",
+                    "Journal enabled",
+                ),
+                JournalCommands::Disable => {
+                    (db.disable_journal(), "journal_disabled", "Journal disabled")
+                }
+            };
+            match result {
+                Ok(_) => emit(output, json!({"action": action}), || {
+                    let response = response_style.paint(message).to_string();
+                    let scroll = emoji("📜", ">");
+                    println!("{scroll} {response}");
+                }),
+                Err(e) => return Err(CliError::io("Error updating journal:", e)),
+            }
         }
-        Commands::Tui => tui::main().expect("Couldn't launch TUI"),
-        Commands::Gui => gui::main(),
+        Commands::Profile { command } => match profile_command(command, profile.clone()) {
+            Ok(message) => {
+                let value = match command {
+                    ProfileCommands::List => Value::Array(
+                        Profiles::new()
+                            .list()
+                            .into_iter()
+                            .map(|name| {
+                                let active = Some(&name) == profile.as_ref();
+                                json!({"name": name, "active": active})
+                            })
+                            .collect(),
+                    ),
+                    ProfileCommands::Create { name } => {
+                        json!({"action": "profile_created", "profile": name})
+                    }
+                    ProfileCommands::Switch { name } => {
+                        json!({"action": "profile_switched", "profile": name})
+                    }
+                    ProfileCommands::Delete { name } => {
+                        json!({"action": "profile_deleted", "profile": name})
+                    }
+                };
+                emit(output, value, || match command {
+                    ProfileCommands::List => println!("{message}"),
+                    _ => {
+                        let response = response_style.paint(message).to_string();
+                        let person = emoji("👤", "@");
+                        println!("{person} {response}");
+                    }
+                });
+            }
+            Err(e) => return Err(CliError::io("Error updating profiles:", e)),
+        },
+        Commands::Merge { file } => match merge_file(&mut db, file) {
+            Ok(summary) => emit(output, merge_value("merged", &summary), || {
+                let response = response_style
+                    .paint(format!(
+                        "Merged {file}: {} added, {} updated, {} unchanged",
+                        summary.added.len(),
+                        summary.updated.len(),
+                        summary.unchanged
+                    ))
+                    .to_string();
+                let merge = emoji("🔀", "~");
+                println!("{merge} {response}");
+                print_merge_summary(&summary);
+            }),
+            Err(e) => return Err(CliError::io("Error merging database:", e)),
+        },
+        Commands::Sync { command } => match sync_command(&mut db, command) {
+            Ok((sync, summary)) => {
+                let mut value = match &summary {
+                    Some(summary) => json!({
+                        "action": "synced",
+                        "committed": summary.committed,
+                        "pushed": summary.pushed,
+                    }),
+                    None => json!({"action": "sync_initialized"}),
+                };
+                value["repo"] = json!(sync.repo);
+                if let Some(merged) = summary.as_ref().and_then(|s| s.merged.as_ref()) {
+                    value["merged"] = merge_value("merged", merged);
+                }
+                if let Some(format) = output {
+                    println!("{}", render(format, &value));
+                    return Ok(0);
+                }
+                let repo = sync.repo.display();
+                let sync_emoji = emoji("🔄", "~");
+                let message = match &summary {
+                    None => format!("Syncing through {repo}"),
+                    Some(summary) => match (summary.committed, summary.pushed) {
+                        (_, true) => format!("Synced with {repo}"),
+                        (true, false) => format!("Committed to {repo}, it has no remote to push to"),
+                        (false, false) => format!("Nothing to sync in {repo}"),
+                    },
+                };
+                println!("{sync_emoji} {}", response_style.paint(message));
+                if let Some(merged) = summary.and_then(|summary| summary.merged) {
+                    print_merge_summary(&merged);
+                }
+            }
+            Err(e) => return Err(CliError::io("Error syncing:", e)),
+        },
+        Commands::Export {
+            format,
+            output: file,
+        } => {
+            let result = Export::new(&db).and_then(|export| export.render(*format));
+            let result = match (result, file) {
+                (Ok(data), Some(file)) => std::fs::write(file, data).map(|_| Some(file)),
+                (Ok(data), None) => {
+                    println!("{data}");
+                    Ok(None)
+                }
+                (Err(e), _) => Err(e),
+            };
+            match result {
+                Ok(Some(file)) => emit(output, json!({"action": "exported", "file": file}), || {
+                    let response = response_style.paint("Exported to").to_string();
+                    let package = emoji("📦", ">");
+                    println!("{package} {response} {file}");
+                }),
+                Ok(None) => {}
+                Err(e) => return Err(CliError::io("Error exporting:", e)),
+            }
+        }
+        Commands::Import {
+            from,
+            dry_run,
+            file,
+        } => match import_file(&mut db, *from, file, *dry_run) {
+            Ok(summary) => {
+                let value = json!({
+                    "action": match dry_run {
+                        true => "would_import",
+                        false => "imported",
+                    },
+                    "created": streak_values(&summary.created),
+                    "skipped": summary.skipped,
+                });
+                if let Some(format) = output {
+                    println!("{}", render(format, &value));
+                    return Ok(0);
+                }
+                let verb = match dry_run {
+                    true => "Would import",
+                    false => "Imported",
+                };
+                let response = response_style
+                    .paint(format!(
+                        "{verb} {} streaks, skipped {} already tracked",
+                        summary.created.len(),
+                        summary.skipped.len()
+                    ))
+                    .to_string();
+                let inbox = emoji("📥", "<");
+                println!("{inbox} {response}");
+                if !summary.created.is_empty() {
+                    println!("{}", build_table(summary.created, &db));
+                }
+                for task in &summary.skipped {
+                    println!("  skipped: {task}");
+                }
+            }
+            Err(e) => return Err(CliError::io("Error importing:", e)),
+        },
+        Commands::Encrypt | Commands::Decrypt => {
+            let (result, message) = match &cli.command {
+                Commands::Encrypt if db.encrypted() => (
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::AlreadyExists,
+                        "The database is already encrypted",
+                    )),
+                    "",
+                ),
+                Commands::Encrypt => (
+                    get_passphrase("New passphrase: ", true)
+                        .and_then(|passphrase| db.encrypt(&passphrase)),
+                    "Database encrypted",
+                ),
+                _ if !db.encrypted() => (
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "The database isn't encrypted",
+                    )),
+                    "",
+                ),
+                _ => (db.decrypt(), "Database decrypted"),
+            };
+            let action = match &cli.command {
+                Commands::Encrypt => "encrypted",
+                _ => "decrypted",
+            };
+            match result {
+                Ok(_) => emit(output, json!({"action": action}), || {
+                    let response = response_style.paint(message).to_string();
+                    let lock = emoji("🔒", "!");
+                    println!("{lock} {response}");
+                }),
+                Err(e) => return Err(CliError::io("Error updating database:", e)),
+            }
+        }
+        Commands::Tui => {
+            tui::main(db, profile).map_err(|e| CliError::io("Couldn't launch TUI:", e))?
+        }
+        Commands::Gui => gui::main(db, profile),
     }
+    Ok(0)
 }
 
 #[cfg(test)]
@@ -243,9 +1661,38 @@ mod tests {
     use assert_fs::TempDir;
     use rstest::*;
 
+    use super::get_database_url;
+    use crate::db::Database;
+    use crate::streak::Streak;
+
     #[fixture]
     pub fn command() -> Command {
-        Command::cargo_bin("skidmarks").unwrap()
+        let mut command = Command::cargo_bin("skidmarks").unwrap();
+        // Keep the config file of whoever runs the tests out of them
+        command.env("XDG_CONFIG_HOME", std::env::temp_dir().join("skidmarks-tests"));
+        command
+    }
+
+    #[rstest]
+    #[case("/tmp/streaks.ron", "/tmp/streaks.ron")]
+    #[case("~/streaks.ron", &format!("{}/streaks.ron", dirs::home_dir().unwrap().display()))]
+    #[case("streaks.ron", &format!("{}/streaks.ron", dirs::data_local_dir().unwrap().display()))]
+    fn database_url_paths(#[case] database_url: &str, #[case] expected: &str) {
+        assert_eq!(get_database_url(Some(database_url.to_string()), None), expected);
+    }
+
+    #[rstest]
+    fn where_uses_env(mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-where.ron");
+
+        command
+            .env("SKIDMARKS_DB", &db_url)
+            .arg("where")
+            .assert()
+            .success()
+            .stdout(format!("{db_url}\n"));
+        assert!(!std::path::Path::new(&db_url).exists());
     }
 
     #[rstest]
@@ -298,6 +1745,601 @@ mod tests {
             .success();
     }
 
+    #[rstest]
+    fn journal_and_log(mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-journal.ron");
+
+        self::command()
+            .args(["--database-url", &db_url, "journal", "enable"])
+            .assert()
+            .success();
+        self::command()
+            .args(["--database-url", &db_url, "add", "--task", "Test Streak"])
+            .args(["--frequency", "daily"])
+            .assert()
+            .success();
+
+        let output = command
+            .args(["--database-url", &db_url, "log"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).contains("added"));
+    }
+
+    #[rstest]
+    fn undo_and_redo(#[values("undo", "redo")] action: &str, mut command: Command) {
+        let temp = TempDir::new().unwrap();
+
+        command
+            .arg("--database-url")
+            .arg(format!("{}/{}", temp.path().display(), "test-undo.ron"))
+            .arg(action)
+            .assert()
+            .success();
+    }
+
+    #[rstest]
+    fn merge_command(mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-merge.ron");
+        let other = format!("{}/{}", temp.path().display(), "test-merge-other.ron");
+        let mut other_db = Database::create_from_file(&other, "[]").unwrap();
+        other_db.add(Streak::new_daily("Stretch".to_string())).unwrap();
+        other_db.save().unwrap();
+
+        let output = command
+            .env("SKIDMARKS_DB", &db_url)
+            .arg("merge")
+            .arg(&other)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("1 added, 0 updated, 0 unchanged"));
+        assert!(stdout.contains("added: Stretch"));
+        assert_eq!(Database::new(&db_url).unwrap().num_tasks(), 1);
+    }
+
+    #[rstest]
+    fn export_command(#[values("json", "csv", "ron")] format: &str, mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-export.ron");
+        let output = format!("{}/{}", temp.path().display(), "export.out");
+
+        command
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["export", "--format", format, "--output", &output])
+            .assert()
+            .success();
+        assert!(std::path::Path::new(&output).exists());
+    }
+
+    #[rstest]
+    fn import_command(#[values(true, false)] dry_run: bool, mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-import.ron");
+        let file = format!("{}/{}", temp.path().display(), "habits.csv");
+        std::fs::write(&file, "task,frequency,date\nStretch,daily,2024-08-01\n").unwrap();
+
+        command.env("SKIDMARKS_DB", &db_url).args(["import", "--from", "csv", &file]);
+        if dry_run {
+            command.arg("--dry-run");
+        }
+        command.assert().success();
+
+        let expected = match dry_run {
+            true => 0,
+            false => 1,
+        };
+        assert_eq!(Database::new(&db_url).unwrap().num_tasks(), expected);
+    }
+
+    #[rstest]
+    fn structured_output(mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-output.ron");
+
+        let output = command
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["--output", "json", "add", "--task", "Poop", "--frequency", "daily"])
+            .output()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(value["action"], "added");
+        assert_eq!(value["task"], "Poop");
+        assert_eq!(value["status"], "missed");
+
+        let output = self::command()
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["list", "--output", "tsv"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert!(lines[0].starts_with("id\ttask\tfrequency\tstatus"));
+        assert_eq!(lines.len(), 2);
+        assert!(!stdout.contains('\u{1b}'));
+    }
+
+    #[rstest]
+    #[case(&["get", "fffff"], 2)]
+    #[case(&["check-in", "fffff"], 2)]
+    #[case(&["check-in"], 1)]
+    #[case(&["status"], 5)]
+    #[case(&["status", "--format", "{nope}"], 1)]
+    #[case(&["remove", "fffff"], 2)]
+    #[case(&["remove", "walk"], 2)]
+    #[case(&["remove", "take a walk"], 0)]
+    #[case(&["get", "00e8a"], 3)]
+    #[case(&["log"], 1)]
+    #[case(&["list", "--theme", "no-such-theme"], 1)]
+    #[case(&["list", "--theme", "latte"], 0)]
+    #[case(&["get", "77cbb"], 0)]
+    #[case(&["get", "take a walk"], 0)]
+    #[case(&["check-in", "floss"], 0)]
+    #[case(&["get", "00e8a16c-0"], 0)]
+    fn exit_codes(#[case] args: &[&str], #[case] code: i32, mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-exit-codes.ron");
+        let mut db = Database::create_from_file(
+            &db_url,
+            r#"[(id:"00e8a16c-0edd-4e90-8c3f-2ee7aa6a2210",task:"Poop"),(id:"00e8a16c-1111-4e90-8c3f-2ee7aa6a2210",task:"Floss"),(id:"77cbbb3f-2690-45a9-9a30-94a53556d93e",task:"Take a walk")]"#,
+        )
+        .unwrap();
+        db.save().unwrap();
+
+        command
+            .env("SKIDMARKS_DB", &db_url)
+            .args(args)
+            .assert()
+            .code(code);
+    }
+
+    #[rstest]
+    fn idents_in_errors(mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-idents.ron");
+        let mut db = Database::create_from_file(
+            &db_url,
+            r#"[(id:"00e8a16c-0edd-4e90-8c3f-2ee7aa6a2210",task:"Poop"),(id:"00e8a16c-1111-4e90-8c3f-2ee7aa6a2210",task:"Floss"),(id:"77cbbb3f-2690-45a9-9a30-94a53556d93e",task:"Take a walk")]"#,
+        )
+        .unwrap();
+        db.save().unwrap();
+
+        let output = command
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["get", "00e8"])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains("00e8a16c-0 (Poop), 00e8a16c-1 (Floss)"),
+            "{stderr}"
+        );
+
+        let output = self::command()
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["remove", "walk"])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(2));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains("give the id (77cb) or whole name"),
+            "{stderr}"
+        );
+        assert_eq!(Database::new(&db_url).unwrap().streaks.len(), 3);
+    }
+
+    #[rstest]
+    fn batch_commands(mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-batch.ron");
+        let mut db = Database::create_from_file(
+            &db_url,
+            r#"[(id:"00e8a16c-0edd-4e90-8c3f-2ee7aa6a2210",task:"Poop"),(id:"00e8a16c-1111-4e90-8c3f-2ee7aa6a2210",task:"Floss"),(id:"77cbbb3f-2690-45a9-9a30-94a53556d93e",task:"Take a walk")]"#,
+        )
+        .unwrap();
+        db.save().unwrap();
+        let run = |args: &[&str]| {
+            let output = self::command()
+                .env("SKIDMARKS_DB", &db_url)
+                .args(["--output", "json"])
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{output:?}");
+            serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+        };
+
+        command
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["check-in", "poop", "fffff"])
+            .assert()
+            .code(2);
+        assert_eq!(Database::new(&db_url).unwrap().streaks[0].total_checkins, 0);
+
+        let value = run(&["tag", "morning", "poop", "floss"]);
+        assert_eq!(value.as_array().unwrap().len(), 2);
+        assert_eq!(value[0]["action"], "tagged");
+
+        let value = run(&["check-in", "--tag", "morning", "walk"]);
+        let actions: Vec<&str> = value
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v["action"].as_str().unwrap())
+            .collect();
+        assert_eq!(actions, ["checked_in"; 3]);
+
+        let value = run(&["check-in", "--all-waiting", "--search", "o"]);
+        assert_eq!(value.as_array().unwrap().len(), 0);
+        let value = run(&["check-in", "poop"]);
+        assert_eq!(value[0]["action"], "already_checked_in");
+
+        run(&["remove", "--tag", "morning"]);
+        let db = Database::new(&db_url).unwrap();
+        assert_eq!(db.num_tasks(), 1);
+        assert_eq!(db.streaks[0].task, "Take a walk");
+    }
+
+    #[rstest]
+    fn archive_commands(mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-archive.ron");
+        let mut db = Database::create_from_file(
+            &db_url,
+            r#"[(id:"00e8a16c-0edd-4e90-8c3f-2ee7aa6a2210",task:"Poop",tags:["morning"]),(id:"00e8a16c-1111-4e90-8c3f-2ee7aa6a2210",task:"Floss",tags:["morning"]),(id:"77cbbb3f-2690-45a9-9a30-94a53556d93e",task:"Take a walk")]"#,
+        )
+        .unwrap();
+        db.save().unwrap();
+        let run = |args: &[&str]| {
+            let output = self::command()
+                .env("SKIDMARKS_DB", &db_url)
+                .args(["--output", "json"])
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{output:?}");
+            serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+        };
+        let tasks = |args: &[&str]| -> Vec<String> {
+            run(args)
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|v| v["task"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        let value = run(&["archive", "--tag", "morning"]);
+        assert_eq!(value[0]["action"], "archived");
+        assert_eq!(value[1]["archived"], true);
+        assert_eq!(tasks(&["list"]), ["Take a walk"]);
+        assert_eq!(tasks(&["today"]), ["Take a walk"]);
+        assert_eq!(tasks(&["list", "--archived"]), ["Floss", "Poop"]);
+        self::command()
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["status", "--format", "{total}"])
+            .assert()
+            .stdout("1\n");
+        assert_eq!(run(&["archive", "poop"])[0]["action"], "unchanged");
+
+        run(&["unarchive", "floss"]);
+        assert_eq!(tasks(&["list"]), ["Floss", "Take a walk"]);
+
+        command
+            .env("SKIDMARKS_DB", &db_url)
+            .arg("undo")
+            .assert()
+            .success();
+        assert_eq!(tasks(&["list"]), ["Take a walk"]);
+        run(&["undo"]);
+        assert_eq!(tasks(&["list", "--archived"]), Vec::<String>::new());
+    }
+
+    #[rstest]
+    fn today_command(mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-today.ron");
+        let yesterday = chrono::Local::now().date_naive() - chrono::TimeDelta::days(1);
+        let mut db = Database::create_from_file(
+            &db_url,
+            &format!(
+                r#"[(id:"00e8a16c-0edd-4e90-8c3f-2ee7aa6a2210",task:"Poop"),(id:"00e8a16c-1111-4e90-8c3f-2ee7aa6a2210",task:"Floss",last_checkin:Some("{yesterday}"),current_streak:4),(id:"77cbbb3f-2690-45a9-9a30-94a53556d93e",task:"Take a walk")]"#
+            ),
+        )
+        .unwrap();
+        db.checkin(db.streaks[2].id).unwrap();
+        db.save().unwrap();
+
+        let output = command
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["--output", "tsv", "today"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let rows: Vec<(&str, &str)> = stdout
+            .lines()
+            .skip(1)
+            .map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                (fields[1], *fields.last().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [("Floss", "at_risk"), ("Poop", "to_do"), ("Take a walk", "done")]
+        );
+
+        let output = self::command()
+            .env("SKIDMARKS_DB", &db_url)
+            .arg("today")
+            .output()
+            .unwrap();
+        assert!(String::from_utf8(output.stdout).unwrap().contains("1/3 done"));
+    }
+
+    #[rstest]
+    fn stats_command(mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-stats.ron");
+        let mut db = Database::new(&db_url).unwrap();
+        db.enable_journal().unwrap();
+        db.add(Streak::new_daily("Poop".to_string())).unwrap();
+        db.add(Streak::new_daily("Floss".to_string())).unwrap();
+        db.save().unwrap();
+        db.checkin(db.streaks[0].id).unwrap();
+        db.save().unwrap();
+
+        let output = command
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["--output", "json", "stats"])
+            .output()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(value["streaks"].as_array().unwrap().len(), 2);
+        assert_eq!(value["totals"]["total_checkins"], 1);
+        assert_eq!(value["totals"]["completion"][0]["rate"], 0.5);
+
+        let output = self::command()
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["--output", "json", "stats", "poop"])
+            .output()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(value["task"], "Poop");
+        assert_eq!(value["completion"][3]["rate"], 1.0);
+        assert_eq!(value["breaks"], 0);
+
+        db.disable_journal().unwrap();
+        let output = self::command()
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["stats", "poop"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("skidmarks journal enable"), "{stdout}");
+        let output = self::command()
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["--output", "json", "stats", "poop"])
+            .output()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(value["average_streak"], 1.0);
+        assert_eq!(value["completion"], serde_json::Value::Null);
+    }
+
+    #[rstest]
+    fn calendar_command(mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-calendar.ron");
+        let mut db = Database::new(&db_url).unwrap();
+        db.add(Streak::new_daily("Poop".to_string())).unwrap();
+        db.add(Streak::new_daily("Floss".to_string())).unwrap();
+        db.save().unwrap();
+
+        self::command()
+            .env("SKIDMARKS_DB", &db_url)
+            .arg("calendar")
+            .assert()
+            .code(1);
+
+        db.enable_journal().unwrap();
+        db.checkin(db.streaks[0].id).unwrap();
+        db.save().unwrap();
+        let output = command
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["--output", "json", "calendar"])
+            .output()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(value[0]["ratio"], 0.5);
+        assert_eq!(
+            value[0]["date"],
+            chrono::Local::now().date_naive().to_string()
+        );
+    }
+
+    #[rstest]
+    #[case("2026-13", 1)]
+    #[case("2024-02", 0)]
+    fn month_command(#[case] month: &str, #[case] code: i32, mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-month.ron");
+        let mut db = Database::new(&db_url).unwrap();
+        db.enable_journal().unwrap();
+        db.add(Streak::new_daily("Poop".to_string())).unwrap();
+        db.save().unwrap();
+
+        let output = command
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["--output", "json", "month", "poop", "--month", month])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(code));
+        if code == 0 {
+            let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+            assert_eq!(value.as_array().unwrap().len(), 29);
+            assert_eq!(value[28]["date"], "2024-02-29");
+            assert_eq!(value[28]["status"], "untracked");
+        }
+    }
+
+    #[rstest]
+    fn report_command(mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-report.ron");
+        let report_file = format!("{}/{}", temp.path().display(), "report.html");
+        let mut db = Database::new(&db_url).unwrap();
+        db.enable_journal().unwrap();
+        db.add(Streak::new_daily("Poop".to_string())).unwrap();
+        db.save().unwrap();
+        db.checkin(db.streaks[0].id).unwrap();
+        db.save().unwrap();
+
+        let output = self::command()
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["report", "--period", "month"])
+            .output()
+            .unwrap();
+        let markdown = String::from_utf8(output.stdout).unwrap();
+        assert!(markdown.contains("| Poop | 1 | 100% | 1 | 1 |"));
+
+        let output = command
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["--output", "json", "report", "--format", "html", "--output", &report_file])
+            .output()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(value["action"], "reported");
+        let output = self::command()
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["--output", "json", "report"])
+            .output()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert!(value["report"].as_str().unwrap().contains("| Poop |"));
+        let html = std::fs::read_to_string(&report_file).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<td>Poop</td>"));
+    }
+
+    #[rstest]
+    #[case(&[], Some("1"), false)]
+    #[case(&[], None, false)]
+    #[case(&["--color", "never"], None, false)]
+    #[case(&["--color", "always"], Some("1"), true)]
+    fn color_choice(
+        mut command: Command,
+        #[case] args: &[&str],
+        #[case] no_color: Option<&str>,
+        #[case] colored: bool,
+    ) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-color.ron");
+        let mut db = Database::new(&db_url).unwrap();
+        let mut poop = Streak::new_daily("Poop".to_string());
+        poop.checkin();
+        db.add(poop).unwrap();
+        db.save().unwrap();
+
+        command
+            .env("SKIDMARKS_DB", &db_url)
+            .env("XDG_CONFIG_HOME", temp.path().join("config"))
+            .env_remove("NO_COLOR");
+        if let Some(no_color) = no_color {
+            command.env("NO_COLOR", no_color);
+        }
+        let output = command.args(args).arg("list").output().unwrap();
+        let table = String::from_utf8(output.stdout).unwrap();
+        assert!(table.contains("Poop"));
+        assert_eq!(table.contains('\u{1b}'), colored);
+        assert_eq!(table.contains("✅"), colored);
+        assert_eq!(table.contains("[x]"), !colored);
+    }
+
+    #[rstest]
+    fn config_command(mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let config_home = temp.path().join("config");
+        let db_url = format!("{}/{}", temp.path().display(), "test-config.ron");
+        let mut db = Database::new(&db_url).unwrap();
+        let mut poop = Streak::new_daily("Poop".to_string());
+        poop.checkin();
+        db.add(poop).unwrap();
+        let mut floss = Streak::new_daily("Floss".to_string());
+        floss.last_checkin = Some(chrono::Local::now().date_naive() - chrono::TimeDelta::days(1));
+        db.add(floss).unwrap();
+        db.add(Streak::new_daily("Walk".to_string())).unwrap();
+        db.save().unwrap();
+
+        let skidmarks = |args: &[&str]| {
+            self::command()
+                .env("XDG_CONFIG_HOME", &config_home)
+                .env("SKIDMARKS_DB", &db_url)
+                .args(args)
+                .output()
+                .unwrap()
+        };
+        assert!(skidmarks(&["config", "set", "filter", "waiting"]).status.success());
+        assert!(skidmarks(&["config", "set", "output", "json"]).status.success());
+        assert_eq!(skidmarks(&["config", "set", "week_start", "someday"]).status.code(), Some(1));
+        assert_eq!(skidmarks(&["config", "set", "colour", "red"]).status.code(), Some(1));
+        assert!(config_home.join("skidmarks").join("config.ron").exists());
+
+        let value: serde_json::Value =
+            serde_json::from_slice(&skidmarks(&["config", "get", "filter"]).stdout).unwrap();
+        assert_eq!(value["filter"], "waiting");
+
+        let value: serde_json::Value =
+            serde_json::from_slice(&skidmarks(&["list"]).stdout).unwrap();
+        assert_eq!(value.as_array().unwrap().len(), 1);
+        assert_eq!(value[0]["task"], "Floss");
+
+        let output = command
+            .env("XDG_CONFIG_HOME", &config_home)
+            .env("SKIDMARKS_DB", &db_url)
+            .args(["config", "set", "output"])
+            .output()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(value["key"], "output");
+        assert_eq!(value["value"], serde_json::Value::Null);
+    }
+
+    #[rstest]
+    fn encrypt_and_decrypt(mut command: Command) {
+        let temp = TempDir::new().unwrap();
+        let db_url = format!("{}/{}", temp.path().display(), "test-encrypt.ron");
+        Database::create_from_file(&db_url, "[]").unwrap();
+
+        command
+            .env("SKIDMARKS_DB", &db_url)
+            .env("SKIDMARKS_PASSPHRASE", "correct horse")
+            .arg("encrypt")
+            .assert()
+            .success();
+        assert!(Database::is_encrypted(&db_url));
+
+        self::command()
+            .env("SKIDMARKS_DB", &db_url)
+            .env("SKIDMARKS_PASSPHRASE", "battery staple")
+            .arg("list")
+            .assert()
+            .code(4);
+
+        self::command()
+            .env("SKIDMARKS_DB", &db_url)
+            .env("SKIDMARKS_PASSPHRASE", "correct horse")
+            .arg("decrypt")
+            .assert()
+            .success();
+        assert!(!Database::is_encrypted(&db_url));
+    }
+
     #[rstest]
     fn test_sort_order(
         #[values(
@@ -0,0 +1,83 @@
+use std::fmt::Display;
+
+/// Exit code for errors that don't fit a more specific code, like invalid input
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit code when a streak, profile or file doesn't exist
+pub const EXIT_NOT_FOUND: i32 = 2;
+/// Exit code when an ident matches more than one streak
+pub const EXIT_AMBIGUOUS: i32 = 3;
+/// Exit code when the database can't be read or written
+pub const EXIT_DATABASE: i32 = 4;
+/// Exit code from `status` when some streaks aren't done yet
+pub const EXIT_NOT_DONE: i32 = 5;
+
+/// An error that ends a command, with the exit code to leave with
+#[derive(Debug)]
+pub struct CliError {
+    pub code: i32,
+    /// What was being done, e.g. `Error checking in:`
+    pub context: String,
+    pub message: String,
+}
+
+impl CliError {
+    pub fn new(code: i32, context: &str, message: impl Display) -> Self {
+        Self {
+            code,
+            context: context.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn not_found(message: impl Display) -> Self {
+        Self::new(EXIT_NOT_FOUND, "Not found:", message)
+    }
+
+    pub fn ambiguous(message: impl Display) -> Self {
+        Self::new(EXIT_AMBIGUOUS, "Ambiguous:", message)
+    }
+
+    /// Wrap an I/O error, picking the exit code from its kind
+    pub fn io(context: &str, err: std::io::Error) -> Self {
+        let code = match err.kind() {
+            std::io::ErrorKind::NotFound => EXIT_NOT_FOUND,
+            std::io::ErrorKind::InvalidInput
+            | std::io::ErrorKind::AlreadyExists
+            | std::io::ErrorKind::Unsupported => EXIT_FAILURE,
+            _ => EXIT_DATABASE,
+        };
+        Self::new(code, context, err)
+    }
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.context, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_errors_get_exit_codes() {
+        let err = |kind| std::io::Error::new(kind, "oops");
+        assert_eq!(
+            CliError::io("Error:", err(std::io::ErrorKind::NotFound)).code,
+            EXIT_NOT_FOUND
+        );
+        assert_eq!(
+            CliError::io("Error:", err(std::io::ErrorKind::InvalidInput)).code,
+            EXIT_FAILURE
+        );
+        assert_eq!(
+            CliError::io("Error:", err(std::io::ErrorKind::PermissionDenied)).code,
+            EXIT_DATABASE
+        );
+        assert_eq!(
+            CliError::ambiguous("\"00e8a\" matches 2 streaks").to_string(),
+            "Ambiguous: \"00e8a\" matches 2 streaks"
+        );
+    }
+}
@@ -1,4 +1,9 @@
 pub mod cli;
 pub use cli::get_database_url;
 pub use cli::parse;
+mod calendar;
+mod error;
+mod output;
+mod picker;
+mod status;
 mod table;
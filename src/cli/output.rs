@@ -0,0 +1,141 @@
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+use crate::export::StreakRecord;
+use crate::streak::Streak;
+
+/// Machine-readable output, picked with the global `--output` flag
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// One JSON document: an array for lists, an object otherwise
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// Tab-separated values with a header row
+    Tsv,
+    /// `key: value` lines, with a blank line between records
+    Plain,
+}
+
+/// A streak as a JSON object, with the action that was taken on it first
+pub fn streak_value(action: Option<&str>, streak: &Streak) -> Value {
+    let mut object = Map::new();
+    if let Some(action) = action {
+        object.insert("action".to_string(), Value::from(action));
+    }
+    if let Ok(Value::Object(record)) = serde_json::to_value(StreakRecord::from(streak)) {
+        object.extend(record);
+    }
+    Value::Object(object)
+}
+
+pub fn streak_values(streaks: &[Streak]) -> Value {
+    Value::Array(streaks.iter().map(|s| streak_value(None, s)).collect())
+}
+
+/// A single field, without quotes around strings
+fn field(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn rows(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(items) => items.iter().collect(),
+        other => vec![other],
+    }
+}
+
+/// Render a command's result. Arrays become one record per item.
+pub fn render(format: OutputFormat, value: &Value) -> String {
+    match format {
+        OutputFormat::Json => serde_json::to_string_pretty(value).unwrap_or_default(),
+        OutputFormat::Ndjson => rows(value)
+            .iter()
+            .map(|row| row.to_string())
+            .collect::<Vec<String>>()
+            .join("\n"),
+        OutputFormat::Tsv => {
+            let mut columns: Vec<&String> = vec![];
+            for row in rows(value) {
+                if let Value::Object(object) = row {
+                    for key in object.keys() {
+                        if !columns.contains(&key) {
+                            columns.push(key);
+                        }
+                    }
+                }
+            }
+            let escape = |text: String| text.replace('\t', "\\t").replace('\n', "\\n");
+            let mut lines = vec![columns
+                .iter()
+                .map(|c| c.as_str())
+                .collect::<Vec<&str>>()
+                .join("\t")];
+            for row in rows(value) {
+                let fields = columns
+                    .iter()
+                    .map(|column| escape(row.get(column.as_str()).map(field).unwrap_or_default()))
+                    .collect::<Vec<String>>();
+                lines.push(fields.join("\t"));
+            }
+            lines.join("\n")
+        }
+        OutputFormat::Plain => rows(value)
+            .iter()
+            .map(|row| match row {
+                Value::Object(object) => object
+                    .iter()
+                    .map(|(key, value)| format!("{key}: {}", field(value)))
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+                other => field(other),
+            })
+            .collect::<Vec<String>>()
+            .join("\n\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn render_formats() {
+        let value = json!([
+            {"action": "added", "task": "Poop", "total_checkins": 1},
+            {"action": "added", "task": "Take\ta walk", "total_checkins": null},
+        ]);
+        assert_eq!(
+            render(OutputFormat::Ndjson, &value),
+            "{\"action\":\"added\",\"task\":\"Poop\",\"total_checkins\":1}\n{\"action\":\"added\",\"task\":\"Take\\ta walk\",\"total_checkins\":null}"
+        );
+        assert_eq!(
+            render(OutputFormat::Tsv, &value),
+            "action\ttask\ttotal_checkins\nadded\tPoop\t1\nadded\tTake\\ta walk\t"
+        );
+        assert_eq!(
+            render(OutputFormat::Plain, &value[0]),
+            "action: added\ntask: Poop\ntotal_checkins: 1"
+        );
+        assert_eq!(
+            serde_json::from_str::<Value>(&render(OutputFormat::Json, &value)).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn streak_value_has_status() {
+        let streak = Streak::new_daily("Poop".to_string());
+        let value = streak_value(Some("added"), &streak);
+        let keys: Vec<&String> = value.as_object().unwrap().keys().collect();
+        assert_eq!(keys[0], "action");
+        assert_eq!(value["status"], "missed");
+        assert_eq!(value["frequency"], "daily");
+    }
+}
@@ -0,0 +1,138 @@
+use ansi_term::Style;
+use console::{Key, Term};
+
+use crate::color::styled;
+
+/// Where a picker is after a key press
+#[derive(Debug, PartialEq)]
+pub enum PickerState {
+    Picking,
+    Done(Vec<usize>),
+    Cancelled,
+}
+
+/// A list of items to tick, moved through with the arrow keys or j/k
+#[derive(Debug)]
+pub struct Picker {
+    items: Vec<String>,
+    cursor: usize,
+    selected: Vec<bool>,
+}
+
+impl Picker {
+    pub fn new(items: Vec<String>) -> Self {
+        let selected = vec![false; items.len()];
+        Self {
+            items,
+            cursor: 0,
+            selected,
+        }
+    }
+
+    fn picked(&self) -> Vec<usize> {
+        (0..self.items.len())
+            .filter(|&i| self.selected[i])
+            .collect()
+    }
+
+    pub fn handle(&mut self, key: Key) -> PickerState {
+        match key {
+            Key::ArrowUp | Key::Char('k') => self.cursor = self.cursor.saturating_sub(1),
+            Key::ArrowDown | Key::Char('j') => {
+                self.cursor = (self.cursor + 1).min(self.items.len().saturating_sub(1))
+            }
+            Key::Char(' ') => {
+                if let Some(selected) = self.selected.get_mut(self.cursor) {
+                    *selected = !*selected;
+                }
+            }
+            Key::Char('a') => {
+                let all = self.selected.iter().all(|s| *s);
+                self.selected.iter_mut().for_each(|s| *s = !all);
+            }
+            // Enter with nothing ticked takes the streak under the cursor
+            Key::Enter => match self.picked().is_empty() && !self.items.is_empty() {
+                true => return PickerState::Done(vec![self.cursor]),
+                false => return PickerState::Done(self.picked()),
+            },
+            Key::Escape | Key::Char('q') | Key::CtrlC => return PickerState::Cancelled,
+            _ => {}
+        }
+        PickerState::Picking
+    }
+
+    fn lines(&self) -> Vec<String> {
+        self.items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let mark = match self.selected[i] {
+                    true => "[x]",
+                    false => "[ ]",
+                };
+                match i == self.cursor {
+                    true => styled(Style::new().bold())
+                        .paint(format!("> {mark} {item}"))
+                        .to_string(),
+                    false => format!("  {mark} {item}"),
+                }
+            })
+            .collect()
+    }
+
+    /// Show the picker on `term` until an answer is given, returning the
+    /// indexes of the picked items, or none if it was cancelled
+    pub fn interact(mut self, term: &Term, prompt: &str) -> Result<Vec<usize>, std::io::Error> {
+        term.write_line(prompt)?;
+        term.write_line("  space: tick, a: tick all, enter: check in, esc: cancel")?;
+        term.hide_cursor()?;
+        let result = loop {
+            let lines = self.lines();
+            for line in &lines {
+                term.write_line(line)?;
+            }
+            let state = match term.read_key() {
+                Ok(key) => self.handle(key),
+                Err(err) => {
+                    term.show_cursor()?;
+                    return Err(err);
+                }
+            };
+            term.clear_last_lines(lines.len())?;
+            match state {
+                PickerState::Picking => continue,
+                PickerState::Done(picked) => break picked,
+                PickerState::Cancelled => break vec![],
+            }
+        };
+        term.clear_last_lines(2)?;
+        term.show_cursor()?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pick_with_keys() {
+        let items = vec!["Poop".to_string(), "Floss".to_string(), "Walk".to_string()];
+        let mut picker = Picker::new(items.clone());
+        assert_eq!(picker.handle(Key::Char(' ')), PickerState::Picking);
+        picker.handle(Key::ArrowDown);
+        picker.handle(Key::ArrowDown);
+        picker.handle(Key::ArrowDown);
+        picker.handle(Key::Char(' '));
+        assert_eq!(picker.handle(Key::Enter), PickerState::Done(vec![0, 2]));
+
+        let mut picker = Picker::new(items.clone());
+        picker.handle(Key::Char('j'));
+        assert_eq!(picker.handle(Key::Enter), PickerState::Done(vec![1]));
+
+        let mut picker = Picker::new(items);
+        picker.handle(Key::Char('a'));
+        assert_eq!(picker.picked(), vec![0, 1, 2]);
+        assert_eq!(picker.handle(Key::Escape), PickerState::Cancelled);
+    }
+}
@@ -0,0 +1,119 @@
+use serde::Serialize;
+
+use crate::streak::{Status, Streak};
+
+/// Format used by `status` when none is given
+pub const DEFAULT_STATUS_FORMAT: &str = "{done}/{total} {emoji}";
+
+/// Counts of streaks by status, for prompts and status bars
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct StatusCounts {
+    pub done: usize,
+    pub waiting: usize,
+    pub missed: usize,
+    pub total: usize,
+    /// Longest streak currently running
+    pub longest: u32,
+}
+
+impl StatusCounts {
+    pub fn new(streaks: &[Streak]) -> Self {
+        let mut counts = Self {
+            total: streaks.len(),
+            ..Default::default()
+        };
+        for streak in streaks {
+            match streak.status() {
+                Status::Done => counts.done += 1,
+                Status::Waiting => counts.waiting += 1,
+                Status::Missed => counts.missed += 1,
+            }
+            if streak.status() != Status::Missed {
+                counts.longest = counts.longest.max(streak.current_streak);
+            }
+        }
+        counts
+    }
+
+    /// Streaks not done yet, whether waiting or missed
+    pub fn left(&self) -> usize {
+        self.waiting + self.missed
+    }
+
+    pub fn emoji(&self) -> &str {
+        match (self.left(), self.waiting) {
+            (0, _) => Status::Done.emoji(),
+            (_, 0) => Status::Missed.emoji(),
+            _ => Status::Waiting.emoji(),
+        }
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "done" => Some(self.done.to_string()),
+            "waiting" => Some(self.waiting.to_string()),
+            "missed" => Some(self.missed.to_string()),
+            "left" => Some(self.left().to_string()),
+            "total" => Some(self.total.to_string()),
+            "longest" => Some(self.longest.to_string()),
+            "emoji" => Some(self.emoji().to_string()),
+            _ => None,
+        }
+    }
+
+    /// Fill in `{field}` placeholders in a format. `{{` and `}}` are literal braces.
+    pub fn render(&self, format: &str) -> Result<String, String> {
+        let mut rendered = String::new();
+        let mut chars = format.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    rendered.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    rendered.push('}');
+                }
+                '{' => {
+                    let name: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                    match self.field(name.trim()) {
+                        Some(value) => rendered.push_str(&value),
+                        None => return Err(format!("Unknown field {{{name}}}")),
+                    }
+                }
+                c => rendered.push(c),
+            }
+        }
+        Ok(rendered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeDelta};
+
+    use super::*;
+
+    #[test]
+    fn render_counts() {
+        let today = Local::now().date_naive();
+        let mut done = Streak::new_daily("Poop".to_string());
+        done.checkin();
+        let mut waiting = Streak::new_daily("Floss".to_string());
+        waiting.last_checkin = Some(today - TimeDelta::days(1));
+        waiting.current_streak = 4;
+        let missed = Streak::new_weekly("Run".to_string());
+
+        let counts = StatusCounts::new(&[done.clone(), waiting, missed]);
+        assert_eq!(counts.render(DEFAULT_STATUS_FORMAT).unwrap(), "1/3 ⏳");
+        assert_eq!(
+            counts
+                .render("{left} left, {missed} missed, best {longest} {{x}}")
+                .unwrap(),
+            "2 left, 1 missed, best 4 {x}"
+        );
+        assert!(counts.render("{nope}").is_err());
+        assert_eq!(StatusCounts::new(&[done]).emoji(), "✅");
+    }
+}
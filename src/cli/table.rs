@@ -1,14 +1,19 @@
+use crate::db::Database;
+use crate::journal::Event;
+use crate::stats::{Completion, StreakStats, Totals};
 use crate::streak::Streak;
 use ansi_term::Style;
 use tabled::{builder::Builder, settings::Style as TabledStyle};
 use term_size::dimensions;
-use crate::color::CliStyles;
+use crate::color::{styled, CliStyles};
+use crate::config::Config;
 
-/// Builds table of streaks from list
-pub fn build_table(streaks: Vec<Streak>) -> String {
+/// Builds table of streaks from list, identified by their shortest ident in `db`
+pub fn build_table(streaks: Vec<Streak>, db: &Database) -> String {
     let cli_styles = CliStyles::new();
+    let date_format = Config::current().date_format();
     let mut builder = Builder::new();
-    let header_style = Style::new().italic().fg(cli_styles.table_header_fg);
+    let header_style = styled(Style::new().italic().fg(cli_styles.table_header_fg));
     builder.push_record([
         header_style.paint("\nIdent").to_string(),
         header_style.paint("\nTask").to_string(),
@@ -20,9 +25,10 @@ pub fn build_table(streaks: Vec<Streak>) -> String {
         header_style.paint("\nTotal").to_string(),
     ]);
 
+    // Off a terminal, leave room for 30 columns of task names
     let (width, _) = match dimensions() {
         Some((w, _)) => (w, 0),
-        None => (60, 0),
+        None => (90, 0),
     };
     let width = std::cmp::min(width.saturating_sub(60), 30);
 
@@ -34,24 +40,21 @@ pub fn build_table(streaks: Vec<Streak>) -> String {
         }
         wrapped_text = wrapped_text.trim().to_string();
 
-        let id = &streak.id.to_string()[0..5];
-        let index = Style::new().bold().paint(format!("{}", id));
-        let streak_name = Style::new().bold().paint(wrapped_text);
+        let id = db.short_id(streak.id);
+        let index = styled(Style::new().bold()).paint(format!("{}", id));
+        let streak_name = styled(Style::new().bold()).paint(wrapped_text);
         let frequency = Style::new().paint(format!("{:^6}", &streak.frequency));
         let emoji = Style::new().paint(format!("{:^6}", &streak.emoji_status()));
         let check_in = match &streak.last_checkin {
-            Some(date) => date.to_string(),
+            Some(date) => date.format(date_format).to_string(),
             None => "None".to_string(),
         };
-        let last_checkin = Style::new().bold().paint(format!("{:^13}", check_in));
-        let current_streak = Style::new()
-            .bold()
+        let last_checkin = styled(Style::new().bold()).paint(format!("{:^13}", check_in));
+        let current_streak = styled(Style::new().bold())
             .paint(format!("{:^7}", &streak.current_streak));
-        let longest_streak = Style::new()
-            .bold()
+        let longest_streak = styled(Style::new().bold())
             .paint(format!("{:^7}", &streak.longest_streak));
-        let total_checkins = Style::new()
-            .bold()
+        let total_checkins = styled(Style::new().bold())
             .paint(format!("{:^5}", &streak.total_checkins));
 
         builder.push_record([
@@ -68,3 +71,95 @@ pub fn build_table(streaks: Vec<Streak>) -> String {
 
     builder.build().with(TabledStyle::psql()).to_string()
 }
+
+/// Builds table of journal events
+pub fn build_log_table(events: Vec<Event>, db: &Database) -> String {
+    let cli_styles = CliStyles::new();
+    let mut builder = Builder::new();
+    let header_style = styled(Style::new().italic().fg(cli_styles.table_header_fg));
+    builder.push_record([
+        header_style.paint("When").to_string(),
+        header_style.paint("Action").to_string(),
+        header_style.paint("Ident").to_string(),
+        header_style.paint("Task").to_string(),
+    ]);
+
+    for event in events.iter() {
+        let streak = event.action.streak();
+        let when = event.timestamp.format("%Y-%m-%d %H:%M:%S").to_string();
+        let id = db.short_id(streak.id);
+        builder.push_record([
+            when,
+            event.action.to_string(),
+            styled(Style::new().bold()).paint(id).to_string(),
+            styled(Style::new().bold()).paint(&streak.task).to_string(),
+        ]);
+    }
+
+    builder.build().with(TabledStyle::psql()).to_string()
+}
+
+/// A completion rate as a percentage, or `-` without history
+fn percent(completion: &Option<Vec<Completion>>, index: usize) -> String {
+    match completion {
+        Some(completion) => format!("{:.0}%", completion[index].rate * 100.0),
+        None => "-".to_string(),
+    }
+}
+
+fn or_dash<T: ToString>(value: Option<T>) -> String {
+    value.map_or("-".to_string(), |v| v.to_string())
+}
+
+/// Builds table of streak statistics, with a row for all of them if `totals` is given
+pub fn build_stats_table(
+    stats: &[StreakStats],
+    totals: Option<&Totals>,
+    db: &Database,
+) -> String {
+    let cli_styles = CliStyles::new();
+    let mut builder = Builder::new();
+    let header_style = styled(Style::new().italic().fg(cli_styles.table_header_fg));
+    builder.push_record(
+        [
+            "\nIdent", "\nTask", "\n7d", "\n30d", "\n90d", "\n365d", "Avg\nStreak",
+            "\nBreaks", "Best\nDay", "Worst\nDay",
+        ]
+        .map(|header| header_style.paint(header).to_string()),
+    );
+
+    for stat in stats {
+        let id = db.short_id(stat.id);
+        builder.push_record([
+            styled(Style::new().bold()).paint(id).to_string(),
+            styled(Style::new().bold()).paint(&stat.task).to_string(),
+            percent(&stat.completion, 0),
+            percent(&stat.completion, 1),
+            percent(&stat.completion, 2),
+            percent(&stat.completion, 3),
+            or_dash(stat.average_streak.map(|average| format!("{average:.1}"))),
+            or_dash(stat.breaks),
+            or_dash(stat.best_weekday),
+            or_dash(stat.worst_weekday),
+        ]);
+    }
+
+    if let Some(totals) = totals {
+        builder.push_record([
+            String::new(),
+            styled(Style::new().italic())
+                .paint(format!("All {} streaks", totals.streaks))
+                .to_string(),
+            percent(&totals.completion, 0),
+            percent(&totals.completion, 1),
+            percent(&totals.completion, 2),
+            percent(&totals.completion, 3),
+            "-".to_string(),
+            or_dash(totals.breaks),
+            or_dash(totals.best_weekday),
+            or_dash(totals.worst_weekday),
+        ]);
+    }
+
+    builder.build().with(TabledStyle::psql()).to_string()
+}
@@ -1,32 +1,285 @@
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
 use catppuccin::{self, Flavor};
+use clap::ValueEnum;
 use ratatui::prelude::*;
-static PALETTE: Flavor = catppuccin::PALETTE.mocha;
+use serde::Deserialize;
+
+use crate::config::Config;
+
+/// Built-in themes, one for each catppuccin flavor
+pub const THEMES: [&str; 4] = ["latte", "frappe", "macchiato", "mocha"];
+
+/// Theme used when none is picked
+pub const DEFAULT_THEME: &str = "mocha";
+
+static CURRENT: OnceLock<Theme> = OnceLock::new();
+static COLORS: AtomicBool = AtomicBool::new(true);
+static EMOJI: AtomicBool = AtomicBool::new(true);
+
+/// When the CLI colors its output, picked with `--color`
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum ColorChoice {
+    /// Only on a terminal, and not when `NO_COLOR` is set
+    #[default]
+    Auto,
+    /// Colors and emoji, even when piped
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Turn colors and emoji on or off for the rest of the run
+    pub fn apply(&self) {
+        let terminal = std::io::stdout().is_terminal();
+        let wants_emoji = console::Term::stdout().features().wants_emoji();
+        let no_color = std::env::var_os("NO_COLOR").map(|v| v.to_string_lossy().into_owned());
+        let term = std::env::var_os("TERM").map(|v| v.to_string_lossy().into_owned());
+        let (colors, emoji) =
+            self.enabled(no_color.as_deref(), term.as_deref(), terminal, wants_emoji);
+        COLORS.store(colors, Ordering::Relaxed);
+        EMOJI.store(emoji, Ordering::Relaxed);
+    }
+
+    /// Whether to use colors and emoji. Auto leaves both out when `NO_COLOR`
+    /// is set or `TERM` is `dumb`, colors off a terminal, and emoji where the
+    /// terminal can't show them.
+    fn enabled(
+        &self,
+        no_color: Option<&str>,
+        term: Option<&str>,
+        terminal: bool,
+        wants_emoji: bool,
+    ) -> (bool, bool) {
+        let plain = no_color.is_some_and(|value| !value.is_empty()) || term == Some("dumb");
+        match self {
+            ColorChoice::Auto => (terminal && !plain, wants_emoji && !plain),
+            ColorChoice::Always => (true, true),
+            ColorChoice::Never => (false, false),
+        }
+    }
+}
+
+pub fn colors_enabled() -> bool {
+    COLORS.load(Ordering::Relaxed)
+}
+
+pub fn emoji_enabled() -> bool {
+    EMOJI.load(Ordering::Relaxed)
+}
+
+/// `style`, or no style at all when colors are off
+pub fn styled(style: ansi_term::Style) -> ansi_term::Style {
+    match colors_enabled() {
+        true => style,
+        false => ansi_term::Style::new(),
+    }
+}
+
+/// An emoji, or its ASCII stand-in when emoji are off
+pub fn emoji<'a>(emoji: &'a str, fallback: &'a str) -> &'a str {
+    match emoji_enabled() {
+        true => emoji,
+        false => fallback,
+    }
+}
+
+/// A color as red, green and blue, written `#rrggbb` in theme files
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Rgb(pub u8, pub u8, pub u8);
 
-const fn ansi(color: &catppuccin::Color) -> ansi_term::Color {
-    ansi_term::Colour::RGB(color.rgb.r, color.rgb.g, color.rgb.b)
+impl Rgb {
+    pub fn hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+
+    /// A color part way from this one to `to`
+    fn mix(&self, to: Rgb, amount: f32) -> Rgb {
+        let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+        Rgb(
+            channel(self.0, to.0),
+            channel(self.1, to.1),
+            channel(self.2, to.2),
+        )
+    }
+}
+
+impl TryFrom<String> for Rgb {
+    type Error = String;
+
+    fn try_from(hex: String) -> Result<Self, Self::Error> {
+        let digits = hex.trim_start_matches('#');
+        let channel = |i: usize| {
+            digits
+                .get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        };
+        match (digits.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Rgb(r, g, b)),
+            _ => Err(format!("\"{hex}\" isn't a color like #1e1e2e")),
+        }
+    }
 }
 
-const fn rgb(color: &catppuccin::Color) -> Color {
-    Color::Rgb(color.rgb.r, color.rgb.g, color.rgb.b)
+impl From<&catppuccin::Color> for Rgb {
+    fn from(color: &catppuccin::Color) -> Self {
+        Rgb(color.rgb.r, color.rgb.g, color.rgb.b)
+    }
+}
+
+const fn ansi(color: Rgb) -> ansi_term::Color {
+    ansi_term::Colour::RGB(color.0, color.1, color.2)
+}
+
+const fn rgb(color: Rgb) -> Color {
+    Color::Rgb(color.0, color.1, color.2)
+}
+
+/// The colors every interface is drawn with. Besides the catppuccin flavors,
+/// themes can be loaded from `skidmarks/themes/<name>.ron` in the config directory.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Theme {
+    #[serde(skip)]
+    pub name: String,
+    pub background: Rgb,
+    /// Rows and other raised areas
+    pub surface: Rgb,
+    /// Every other row
+    pub surface_alt: Rgb,
+    pub text: Rgb,
+    /// Headers, highlights and the selected tab
+    pub accent: Rgb,
+    /// The full end of the heatmap
+    pub success: Rgb,
+    pub danger: Rgb,
+}
+
+impl Theme {
+    fn from_flavor(name: &str, flavor: &Flavor) -> Self {
+        Theme {
+            name: name.to_string(),
+            background: (&flavor.colors.base).into(),
+            surface: (&flavor.colors.surface0).into(),
+            surface_alt: (&flavor.colors.surface1).into(),
+            text: (&flavor.colors.text).into(),
+            accent: (&flavor.colors.peach).into(),
+            success: (&flavor.colors.green).into(),
+            danger: (&flavor.colors.red).into(),
+        }
+    }
+
+    pub fn dir() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_default()
+            .join("skidmarks")
+            .join("themes")
+    }
+
+    /// Built-in themes, then any in the themes directory by name
+    pub fn names() -> Vec<String> {
+        let mut custom: Vec<String> = std::fs::read_dir(Self::dir())
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+                    .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string()))
+                    .filter(|name| !THEMES.contains(&name.as_str()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        custom.sort();
+        THEMES
+            .iter()
+            .map(|name| name.to_string())
+            .chain(custom)
+            .collect()
+    }
+
+    /// Read a theme from the text of a theme file
+    pub fn parse(name: &str, contents: &str) -> Result<Self, String> {
+        let mut theme: Theme = ron::de::from_str(contents)
+            .map_err(|err| format!("Theme \"{name}\" is invalid: {err}"))?;
+        theme.name = name.to_string();
+        Ok(theme)
+    }
+
+    pub fn load(name: &str) -> Result<Self, String> {
+        let palette = &catppuccin::PALETTE;
+        match name {
+            "latte" => Ok(Self::from_flavor(name, &palette.latte)),
+            "frappe" => Ok(Self::from_flavor(name, &palette.frappe)),
+            "macchiato" => Ok(Self::from_flavor(name, &palette.macchiato)),
+            "mocha" => Ok(Self::from_flavor(name, &palette.mocha)),
+            _ => {
+                let path = Self::dir().join(format!("{name}.ron"));
+                let contents = std::fs::read_to_string(&path).map_err(|_| {
+                    format!(
+                        "No theme \"{name}\", expected one of {}",
+                        Self::names().join(", ")
+                    )
+                })?;
+                Self::parse(name, &contents)
+            }
+        }
+    }
+
+    /// The theme after this one in `names`, wrapping around
+    pub fn next_name(&self) -> String {
+        let names = Self::names();
+        let at = names.iter().position(|name| *name == self.name);
+        let next = at.map_or(0, |at| (at + 1) % names.len());
+        names[next].clone()
+    }
+
+    /// The theme picked with `--theme` or in the config file, or mocha
+    pub fn current() -> &'static Self {
+        CURRENT.get_or_init(|| {
+            Config::current()
+                .theme
+                .as_deref()
+                .and_then(|name| Self::load(name).ok())
+                .unwrap_or_else(|| Self::from_flavor(DEFAULT_THEME, &catppuccin::PALETTE.mocha))
+        })
+    }
+
+    /// Use a theme for the rest of the run. Only works before anything is drawn.
+    pub fn select(name: &str) -> Result<(), String> {
+        let theme = Self::load(name)?;
+        let _ = CURRENT.set(theme);
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct CliStyles {
     pub response_fg: ansi_term::Color,
     pub response_error_fg: ansi_term::Color,
-    pub table_header_fg: ansi_term::Color
+    pub table_header_fg: ansi_term::Color,
+    /// Heatmap cells from no check-ins to all of them
+    pub heatmap: [ansi_term::Color; 5],
 }
 
 impl CliStyles {
     pub fn new() -> Self {
+        let theme = Theme::current();
         CliStyles {
-            response_fg: ansi(&PALETTE.colors.text),
-            response_error_fg: ansi(&PALETTE.colors.red),
-            table_header_fg: ansi(&PALETTE.colors.peach),
+            response_fg: ansi(theme.text),
+            response_error_fg: ansi(theme.danger),
+            table_header_fg: ansi(theme.accent),
+            heatmap: heatmap(theme).map(ansi),
         }
     }
 }
 
+/// Heatmap colors from the surface color toward the success color
+fn heatmap(theme: &Theme) -> [Rgb; 5] {
+    [0.0, 0.35, 0.6, 0.8, 1.0].map(|amount| theme.surface.mix(theme.success, amount))
+}
+
 #[derive(Clone, Debug)]
 pub struct TuiStyles {
     pub background: Color,
@@ -44,34 +297,26 @@ pub struct TuiStyles {
 
 impl TuiStyles {
     pub fn new() -> Self {
-        let peach = rgb(&PALETTE.colors.peach);
-        let text = rgb(&PALETTE.colors.text);
-        let base = rgb(&PALETTE.colors.base);
+        Self::from_theme(Theme::current())
+    }
+
+    pub fn from_theme(theme: &Theme) -> Self {
+        let accent = rgb(theme.accent);
+        let text = rgb(theme.text);
+        let background = rgb(theme.background);
 
         TuiStyles {
-            background: base,
+            background,
             foreground: text,
-            danger: Color::Rgb(
-                PALETTE.colors.red.rgb.r,
-                PALETTE.colors.red.rgb.g,
-                PALETTE.colors.red.rgb.b,
-            ),
-            row_bg: Color::Rgb(
-                PALETTE.colors.surface0.rgb.r,
-                PALETTE.colors.surface0.rgb.g,
-                PALETTE.colors.surface0.rgb.b,
-            ),
-            alt_row_bg: Color::Rgb(
-                PALETTE.colors.surface1.rgb.r,
-                PALETTE.colors.surface1.rgb.g,
-                PALETTE.colors.surface1.rgb.b,
-            ),
+            danger: rgb(theme.danger),
+            row_bg: rgb(theme.surface),
+            alt_row_bg: rgb(theme.surface_alt),
             row_fg: text,
             alt_row_fg: text,
-            highlight_bg: peach,
-            highlight_fg: base,
+            highlight_bg: accent,
+            highlight_fg: background,
             tab_fg: text,
-            selected_tab_fg: peach,
+            selected_tab_fg: accent,
         }
     }
 }
@@ -86,11 +331,107 @@ pub struct GuiStyles {
 
 impl GuiStyles {
     pub fn new() -> Self {
+        let theme = Theme::current();
         GuiStyles {
-            header_bg: rgb(&PALETTE.colors.peach),
-            header_fg: rgb(&PALETTE.colors.surface0),
-            background: PALETTE.colors.base.hex.to_string(),
-            foreground: PALETTE.colors.text.hex.to_string(),
+            header_bg: rgb(theme.accent),
+            header_fg: rgb(theme.surface),
+            background: theme.background.hex(),
+            foreground: theme.text.hex(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ReportStyles {
+    pub background: String,
+    pub foreground: String,
+    pub accent: String,
+    pub surface: String,
+    /// Heatmap cells from no check-ins to all of them
+    pub heatmap: [String; 5],
+}
+
+impl ReportStyles {
+    pub fn new() -> Self {
+        let theme = Theme::current();
+        ReportStyles {
+            background: theme.background.hex(),
+            foreground: theme.text.hex(),
+            accent: theme.accent.hex(),
+            surface: theme.surface.hex(),
+            heatmap: heatmap(theme).map(|color| color.hex()),
         }
     }
 }
+
+impl Default for ReportStyles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_choice() {
+        let xterm = Some("xterm-256color");
+        assert_eq!(
+            ColorChoice::Auto.enabled(None, xterm, true, true),
+            (true, true)
+        );
+        assert_eq!(
+            ColorChoice::Auto.enabled(None, None, false, false),
+            (false, false)
+        );
+        assert_eq!(
+            ColorChoice::Never.enabled(None, xterm, true, true),
+            (false, false)
+        );
+
+        assert_eq!(
+            ColorChoice::Auto.enabled(Some("1"), xterm, true, true),
+            (false, false)
+        );
+        assert_eq!(
+            ColorChoice::Auto.enabled(Some(""), xterm, true, true),
+            (true, true)
+        );
+        assert_eq!(
+            ColorChoice::Always.enabled(Some("1"), xterm, false, false),
+            (true, true)
+        );
+
+        assert_eq!(
+            ColorChoice::Auto.enabled(None, Some("dumb"), true, true),
+            (false, false)
+        );
+    }
+
+    #[test]
+    fn themes() {
+        let mocha = Theme::load("mocha").unwrap();
+        assert_eq!(mocha.background.hex(), "#1e1e2e");
+        assert_eq!(Theme::load("latte").unwrap().next_name(), "frappe");
+        assert!(Theme::load("no-such-theme").is_err());
+
+        let solarized = Theme::parse(
+            "solarized",
+            r##"(
+                background: "#fdf6e3",
+                surface: "#eee8d5",
+                surface_alt: "#e4ddc8",
+                text: "#657b83",
+                accent: "#cb4b16",
+                success: "#859900",
+                danger: "#dc322f",
+            )"##,
+        )
+        .unwrap();
+        assert_eq!(solarized.name, "solarized");
+        assert_eq!(solarized.accent, Rgb(0xcb, 0x4b, 0x16));
+        assert_eq!(heatmap(&solarized)[4], solarized.success);
+        assert!(Theme::parse("broken", r#"(background: "white")"#).is_err());
+    }
+}
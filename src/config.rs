@@ -0,0 +1,267 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, Weekday};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::filtering::FilterByStatus;
+use crate::sorting::{get_sort_order, SortByDirection, SortByField};
+
+/// Keys `skidmarks config get` and `set` know about
+pub const KEYS: [&str; 9] = [
+    "database",
+    "default_profile",
+    "sort_by",
+    "filter",
+    "theme",
+    "date_format",
+    "week_start",
+    "rollover",
+    "output",
+];
+
+/// Format for dates when `date_format` isn't set
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// User preferences, read from `skidmarks/config.ron` in the platform config directory
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Database file to use when neither `--database-url` nor `SKIDMARKS_DB` is set
+    pub database: Option<String>,
+    /// Profile to use when `--profile` isn't given
+    pub default_profile: Option<String>,
+    /// Sort order for lists, like `task+` or `current_streak-`
+    pub sort_by: Option<String>,
+    /// Status lists are filtered by until another is picked
+    pub filter: Option<FilterByStatus>,
+    /// A catppuccin flavor, or the name of a theme in the themes directory
+    pub theme: Option<String>,
+    /// strftime format for dates shown in lists
+    pub date_format: Option<String>,
+    /// First day of the week in calendars and weekly reports
+    pub week_start: Option<Weekday>,
+    /// Time of day a new day starts, so late check-ins count for the day before
+    pub rollover: Option<NaiveTime>,
+    /// Output format to use when `--output` isn't given
+    pub output: Option<String>,
+}
+
+static CURRENT: OnceLock<Config> = OnceLock::new();
+
+/// Today's date, taking the configured rollover time into account
+pub fn today() -> NaiveDate {
+    Config::current().today()
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_default()
+            .join("skidmarks")
+            .join("config.ron")
+    }
+
+    /// Load the config file, falling back to defaults if it is missing or invalid
+    pub fn load() -> Self {
+        std::fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|contents| ron::de::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// The config as it was when first asked for, read once per run. Unit
+    /// tests get the defaults, so they don't depend on the config file.
+    pub fn current() -> &'static Self {
+        CURRENT.get_or_init(|| match cfg!(test) {
+            true => Self::default(),
+            false => Self::load(),
+        })
+    }
+
+    pub fn save(&self) -> Result<(), std::io::Error> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let encoded = ron::ser::to_string_pretty(self, PrettyConfig::default())
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        std::fs::write(path, encoded)
+    }
+
+    pub fn sort_order(&self) -> (SortByField, SortByDirection) {
+        get_sort_order(self.sort_by.as_deref().unwrap_or("task+"))
+    }
+
+    pub fn filter(&self) -> FilterByStatus {
+        self.filter.clone().unwrap_or(FilterByStatus::All)
+    }
+
+    pub fn date_format(&self) -> &str {
+        self.date_format.as_deref().unwrap_or(DEFAULT_DATE_FORMAT)
+    }
+
+    pub fn week_start(&self) -> Weekday {
+        self.week_start.unwrap_or(Weekday::Mon)
+    }
+
+    /// The day a moment counts for, which is the day before until the rollover time
+    pub fn day_of(&self, moment: DateTime<Local>) -> NaiveDate {
+        let moment = moment.naive_local();
+        match self.rollover {
+            Some(rollover) => (moment - rollover.signed_duration_since(NaiveTime::MIN)).date(),
+            None => moment.date(),
+        }
+    }
+
+    pub fn today(&self) -> NaiveDate {
+        self.day_of(Local::now())
+    }
+
+    /// A setting as text, or `None` if it isn't set
+    pub fn get(&self, key: &str) -> Result<Option<String>, String> {
+        let value = match key {
+            "database" => self.database.clone(),
+            "default_profile" => self.default_profile.clone(),
+            "sort_by" => self.sort_by.clone(),
+            "filter" => self
+                .filter
+                .as_ref()
+                .map(|filter| format!("{filter:?}").to_lowercase()),
+            "theme" => self.theme.clone(),
+            "date_format" => self.date_format.clone(),
+            "week_start" => self.week_start.map(|day| format!("{day:?}").to_lowercase()),
+            "rollover" => self.rollover.map(|time| time.format("%H:%M").to_string()),
+            "output" => self.output.clone(),
+            _ => return Err(unknown_key(key)),
+        };
+        Ok(value)
+    }
+
+    /// Change a setting from text, or go back to the default with `None`
+    pub fn set(&mut self, key: &str, value: Option<&str>) -> Result<(), String> {
+        let value = value.map(str::trim);
+        match key {
+            "database" => self.database = value.map(String::from),
+            "default_profile" => self.default_profile = value.map(String::from),
+            "sort_by" => self.sort_by = value.map(parse_sort_by).transpose()?,
+            "filter" => self.filter = value.map(parse_filter).transpose()?,
+            "theme" => self.theme = value.map(parse_theme).transpose()?,
+            "date_format" => self.date_format = value.map(parse_date_format).transpose()?,
+            "week_start" => {
+                self.week_start = value
+                    .map(|day| {
+                        day.parse::<Weekday>()
+                            .map_err(|_| format!("\"{day}\" isn't a day of the week"))
+                    })
+                    .transpose()?
+            }
+            "rollover" => {
+                self.rollover = value
+                    .map(|time| {
+                        NaiveTime::parse_from_str(time, "%H:%M")
+                            .map_err(|_| format!("\"{time}\" isn't a time like 04:00"))
+                    })
+                    .transpose()?
+            }
+            "output" => self.output = value.map(String::from),
+            _ => return Err(unknown_key(key)),
+        }
+        Ok(())
+    }
+}
+
+fn unknown_key(key: &str) -> String {
+    format!(
+        "Unknown setting \"{key}\", expected one of {}",
+        KEYS.join(", ")
+    )
+}
+
+fn parse_sort_by(sort_by: &str) -> Result<String, String> {
+    let field = sort_by.trim_end_matches(['+', '-']);
+    let known = SortByField::from_str(field).to_string() == field;
+    match known && sort_by.len() == field.len() + 1 {
+        true => Ok(sort_by.to_string()),
+        false => Err(format!(
+            "\"{sort_by}\" isn't a sort order like task+ or current_streak-, the fields are \
+             task, frequency, status, last_checkin, current_streak, longest_streak and total_checkins"
+        )),
+    }
+}
+
+fn parse_filter(filter: &str) -> Result<FilterByStatus, String> {
+    match filter.to_lowercase().as_str() {
+        "all" => Ok(FilterByStatus::All),
+        "done" => Ok(FilterByStatus::Done),
+        "waiting" => Ok(FilterByStatus::Waiting),
+        "missed" => Ok(FilterByStatus::Missed),
+        _ => Err(format!(
+            "\"{filter}\" isn't a filter, expected all, done, waiting or missed"
+        )),
+    }
+}
+
+fn parse_theme(theme: &str) -> Result<String, String> {
+    crate::color::Theme::load(theme).map(|theme| theme.name)
+}
+
+fn parse_date_format(format: &str) -> Result<String, String> {
+    let invalid =
+        chrono::format::StrftimeItems::new(format).any(|item| item == chrono::format::Item::Error);
+    match invalid {
+        true => Err(format!("\"{format}\" isn't a valid date format")),
+        false => Ok(format.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn get_and_set() {
+        let mut config = Config::default();
+        assert_eq!(config.get("sort_by"), Ok(None));
+        assert_eq!(
+            config.sort_order(),
+            (SortByField::Task, SortByDirection::Ascending)
+        );
+
+        config.set("sort_by", Some("current_streak-")).unwrap();
+        config.set("filter", Some("Waiting")).unwrap();
+        config.set("week_start", Some("sunday")).unwrap();
+        config.set("rollover", Some("04:30")).unwrap();
+        config.set("date_format", Some("%d/%m/%Y")).unwrap();
+        assert_eq!(
+            config.sort_order(),
+            (SortByField::CurrentStreak, SortByDirection::Descending)
+        );
+        assert_eq!(config.filter(), FilterByStatus::Waiting);
+        assert_eq!(config.get("filter"), Ok(Some("waiting".to_string())));
+        assert_eq!(config.week_start(), Weekday::Sun);
+        assert_eq!(config.get("week_start"), Ok(Some("sun".to_string())));
+        assert_eq!(config.get("rollover"), Ok(Some("04:30".to_string())));
+        let late = Local.with_ymd_and_hms(2024, 8, 6, 1, 0, 0).unwrap();
+        assert_eq!(
+            config.day_of(late),
+            NaiveDate::from_ymd_opt(2024, 8, 5).unwrap()
+        );
+        assert_eq!(config.date_format(), "%d/%m/%Y");
+
+        config.set("date_format", None).unwrap();
+        assert_eq!(config.date_format(), DEFAULT_DATE_FORMAT);
+
+        assert!(config.set("sort_by", Some("task")).is_err());
+        assert!(config.set("sort_by", Some("colour+")).is_err());
+        assert!(config.set("sort_by", Some("task+-")).is_err());
+        assert!(config.set("filter", Some("sometimes")).is_err());
+        assert!(config.set("theme", Some("neon")).is_err());
+        assert!(config.set("rollover", Some("late")).is_err());
+        assert!(config.set("date_format", Some("%Q")).is_err());
+        assert!(config.get("colour").is_err());
+    }
+}
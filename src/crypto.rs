@@ -0,0 +1,170 @@
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+/// Marks the start of an encrypted file
+const MAGIC: &[u8] = b"skidmarks-encrypted-v1\n";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// The salt, nonce and ciphertext of an encrypted file
+type Parts<'a> = (&'a [u8], &'a [u8], &'a [u8]);
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key, std::io::Error> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err.to_string()))?;
+    Ok(key)
+}
+
+/// Passphrase-based encryption for files at rest.
+///
+/// Files are laid out as the magic line, the salt, the nonce and then the
+/// ciphertext. The key derived for our own salt is kept so repeated saves
+/// don't pay for key derivation every time.
+#[derive(Clone)]
+pub struct Cipher {
+    passphrase: String,
+    salt: [u8; SALT_LEN],
+    key: Key,
+}
+
+impl std::fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cipher").finish_non_exhaustive()
+    }
+}
+
+impl Cipher {
+    pub fn new(passphrase: &str) -> Result<Self, std::io::Error> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self::with_salt(passphrase, salt)
+    }
+
+    fn with_salt(passphrase: &str, salt: [u8; SALT_LEN]) -> Result<Self, std::io::Error> {
+        Ok(Self {
+            passphrase: passphrase.to_string(),
+            salt,
+            key: derive_key(passphrase, &salt)?,
+        })
+    }
+
+    pub fn passphrase(&self) -> &str {
+        &self.passphrase
+    }
+
+    /// Set up a cipher matching an existing encrypted file
+    pub fn for_data(passphrase: &str, data: &[u8]) -> Result<Self, std::io::Error> {
+        let salt = Self::split(data)?.0;
+        let cipher = Self::with_salt(passphrase, salt.try_into().unwrap())?;
+        cipher.decrypt(data)?;
+        Ok(cipher)
+    }
+
+    fn split(data: &[u8]) -> Result<Parts<'_>, std::io::Error> {
+        let body = data.strip_prefix(MAGIC).filter(|body| body.len() >= SALT_LEN + NONCE_LEN);
+        match body {
+            Some(body) => {
+                let (salt, rest) = body.split_at(SALT_LEN);
+                let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+                Ok((salt, nonce, ciphertext))
+            }
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Not an encrypted skidmarks file",
+            )),
+        }
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = ChaCha20Poly1305::new(&self.key)
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| std::io::Error::other("Could not encrypt data"))?;
+
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&self.salt);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        Ok(data)
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+        let (salt, nonce, ciphertext) = Self::split(data)?;
+        let key = match salt == self.salt {
+            true => self.key,
+            false => derive_key(&self.passphrase, salt)?,
+        };
+        ChaCha20Poly1305::new(&key)
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::PermissionDenied, "Wrong passphrase")
+            })
+    }
+}
+
+/// Turn file contents into text, decrypting them if needed
+pub fn decode(data: Vec<u8>, cipher: Option<&Cipher>) -> Result<String, std::io::Error> {
+    let data = match (is_encrypted(&data), cipher) {
+        (false, _) => data,
+        (true, Some(cipher)) => cipher.decrypt(&data)?,
+        (true, None) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "This file is encrypted, a passphrase is needed",
+            ))
+        }
+    };
+    String::from_utf8(data)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+}
+
+/// Turn text into file contents, encrypting them if a cipher is given
+pub fn encode(text: &str, cipher: Option<&Cipher>) -> Result<Vec<u8>, std::io::Error> {
+    match cipher {
+        Some(cipher) => cipher.encrypt(text.as_bytes()),
+        None => Ok(text.as_bytes().to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let cipher = Cipher::new("correct horse").unwrap();
+        let data = encode("[]", Some(&cipher)).unwrap();
+        assert!(is_encrypted(&data));
+        assert_eq!(decode(data.clone(), Some(&cipher)).unwrap(), "[]");
+
+        let reopened = Cipher::for_data("correct horse", &data).unwrap();
+        assert_eq!(decode(data, Some(&reopened)).unwrap(), "[]");
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let cipher = Cipher::new("correct horse").unwrap();
+        let data = cipher.encrypt(b"[]").unwrap();
+        let result = Cipher::for_data("battery staple", &data);
+        assert_eq!(
+            result.unwrap_err().kind(),
+            std::io::ErrorKind::PermissionDenied
+        );
+    }
+
+    #[test]
+    fn plain_text_passes_through() {
+        let data = encode("[]", None).unwrap();
+        assert!(!is_encrypted(&data));
+        assert_eq!(decode(data, None).unwrap(), "[]");
+        assert!(decode(Cipher::new("x").unwrap().encrypt(b"[]").unwrap(), None).is_err());
+    }
+}
@@ -1,15 +1,80 @@
+use std::ffi::OsString;
 use std::fs::{File, OpenOptions};
-use std::io::Write;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread::sleep;
+use std::time::Duration;
 
+use chrono::{Local, NaiveDate};
+
+use crate::crypto::{self, Cipher};
 use crate::filtering::{filter_by_status, FilterByStatus};
+use crate::history::{Change, History, Origin};
+use crate::journal::Journal;
 use crate::sorting::{SortByDirection, SortByField};
+use crate::stats::CheckinHistory;
 use crate::streak::{sort_streaks, Streak};
+use fs2::FileExt;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use uuid::Uuid;
 
+/// Shortest id prefix that is looked up as an id rather than a task name
+pub const MIN_IDENT_LEN: usize = 4;
+/// How many times to retry taking the file lock before giving up
+const LOCK_ATTEMPTS: u32 = 20;
+/// How long to wait between lock attempts
+const LOCK_RETRY_DELAY: Duration = Duration::from_millis(50);
+
 #[derive(Debug)]
 pub struct Database {
     pub filename: String,
     pub streaks: Vec<Streak>,
+    /// Streaks as they were on disk when last loaded or saved
+    base: Vec<Streak>,
+    /// Set when the database file is encrypted
+    cipher: Option<Cipher>,
+    /// Earlier check-ins to record in the journal with the next save
+    backfills: Vec<(Uuid, Vec<NaiveDate>)>,
+}
+
+/// What merging in streaks from another database file changed
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MergeSummary {
+    pub added: Vec<Streak>,
+    pub updated: Vec<Streak>,
+    pub unchanged: usize,
+    /// Streaks checked in on in both copies whose check-ins couldn't be
+    /// combined, because a copy has no journal covering all of them. These
+    /// keep the higher counts.
+    pub conflicts: Vec<Streak>,
+}
+
+/// Watches the database file and reports when it has been changed on disk
+pub struct DatabaseWatcher {
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    file_name: OsString,
+}
+
+impl DatabaseWatcher {
+    /// Drain pending events, returning true if any of them touched the database file
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        while let Ok(event) = self.events.try_recv() {
+            let Ok(event) = event else { continue };
+            let is_write = matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            );
+            let is_ours = event
+                .paths
+                .iter()
+                .any(|p| p.file_name() == Some(self.file_name.as_os_str()));
+            changed |= is_write && is_ours;
+        }
+        changed
+    }
 }
 
 impl Clone for Database {
@@ -17,6 +82,9 @@ impl Clone for Database {
         Self {
             filename: self.filename.clone(),
             streaks: self.streaks.clone(),
+            base: self.base.clone(),
+            cipher: self.cipher.clone(),
+            backfills: self.backfills.clone(),
         }
     }
 }
@@ -56,39 +124,377 @@ impl Database {
         self.streaks.len()
     }
 
-    fn load_database(filename: &str) -> Result<Vec<Streak>, std::io::Error> {
+    /// Take an advisory lock on the database file, retrying for a short while
+    /// if another process is holding it.
+    fn lock(file: &File, filename: &str, exclusive: bool) -> Result<(), std::io::Error> {
+        for _ in 0..LOCK_ATTEMPTS {
+            let result = match exclusive {
+                true => FileExt::try_lock_exclusive(file),
+                false => FileExt::try_lock_shared(file),
+            };
+            match result {
+                Ok(_) => return Ok(()),
+                Err(err) if err.kind() == fs2::lock_contended_error().kind() => {
+                    sleep(LOCK_RETRY_DELAY)
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Err(std::io::Error::new(
+            std::io::ErrorKind::WouldBlock,
+            format!("{filename} is locked by another skidmarks process, try again"),
+        ))
+    }
+
+    fn read_streaks(file: &mut File, cipher: Option<&Cipher>) -> Result<Vec<Streak>, std::io::Error> {
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        Self::parse_streaks(data, cipher)
+    }
+
+    fn parse_streaks(data: Vec<u8>, cipher: Option<&Cipher>) -> Result<Vec<Streak>, std::io::Error> {
+        let contents = crypto::decode(data, cipher)?;
+        if contents.trim().is_empty() {
+            return Ok(vec![]);
+        }
+        // Refuse to go on with a file we can't read, or saving would replace it
+        ron::de::from_str(&contents)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+
+    /// Replace the file's contents, returning what was written
+    fn write_streaks(
+        file: &mut File,
+        streaks: &[Streak],
+        cipher: Option<&Cipher>,
+    ) -> Result<Vec<u8>, std::io::Error> {
+        let encoded = ron::ser::to_string(streaks)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        let data = crypto::encode(&encoded, cipher)?;
+
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&data)?;
+        file.flush()?;
+        Ok(data)
+    }
+
+    fn load_database(
+        filename: &str,
+        cipher: Option<&Cipher>,
+    ) -> Result<Vec<Streak>, std::io::Error> {
         Self::create_if_missing(filename)?;
-        let contents = std::fs::read_to_string(filename)?;
-        let decoded: Vec<Streak> =
-            ron::de::from_str(&contents).unwrap_or_else(|_| Vec::<Streak>::new());
-        Ok(decoded)
-    }
-
-    fn save_database(&self, filename: &str) {
-        let streaks: Vec<Streak> = self.streaks.clone();
-        let encoded = ron::ser::to_string(&streaks).unwrap();
-        let mut file = OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open(filename)
-            .unwrap();
-        file.write_all(encoded.as_bytes()).unwrap();
+        let mut file = File::open(filename)?;
+        Self::lock(&file, filename, false)?;
+        match Journal::open(filename) {
+            Some(journal) => {
+                Self::read_journal(&journal, &mut file, cipher).map(|(_, streaks)| streaks)
+            }
+            None => Self::read_streaks(&mut file, cipher),
+        }
+    }
+
+    /// Replay the journal. Returns the replayed streaks and the current
+    /// ones, which are the database file's instead if it was edited by hand
+    /// or by a sync tool since skidmarks last wrote it.
+    fn read_journal(
+        journal: &Journal,
+        file: &mut File,
+        cipher: Option<&Cipher>,
+    ) -> Result<(Vec<Streak>, Vec<Streak>), std::io::Error> {
+        let replayed = journal.replay()?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        let current = match journal.edited_outside(&data) {
+            true => Self::parse_streaks(data, cipher)?,
+            false => replayed.clone(),
+        };
+        Ok((replayed, current))
+    }
+
+    /// Apply our changes since the last load on top of what is on disk now, so
+    /// edits made by another process in the meantime are kept.
+    fn merge(base: &[Streak], ours: &[Streak], theirs: Vec<Streak>) -> Vec<Streak> {
+        let mut merged = theirs;
+        merged.retain(|s| {
+            ours.iter().any(|o| o.id == s.id) || !base.iter().any(|b| b.id == s.id)
+        });
+        for streak in ours {
+            if base.contains(streak) {
+                continue;
+            }
+            match merged.iter_mut().find(|s| s.id == streak.id) {
+                Some(existing) => *existing = streak.clone(),
+                None => merged.push(streak.clone()),
+            }
+        }
+        merged
+    }
+
+    /// Write to disk while holding the lock. Regular changes are merged with
+    /// what is on disk and remembered for undo; undo and redo instead replay
+    /// a change from the history. Returns the change that was applied.
+    fn save_database(
+        &mut self,
+        filename: &str,
+        origin: Origin,
+    ) -> Result<Option<Change>, std::io::Error> {
+        Self::create_if_missing(filename)?;
+        let mut file = OpenOptions::new().read(true).write(true).open(filename)?;
+        Self::lock(&file, filename, true)?;
+
+        let journal = Journal::open(filename);
+        let on_disk = match &journal {
+            Some(journal) => {
+                let (replayed, current) =
+                    Self::read_journal(journal, &mut file, self.cipher.as_ref())?;
+                // Edits made to the file outside skidmarks are logged before ours
+                journal.record_outside_edits(&replayed, &current)?;
+                current
+            }
+            None => Self::read_streaks(&mut file, self.cipher.as_ref())?,
+        };
+
+        let mut history = History::load(filename, self.cipher.as_ref());
+        let mut streaks = on_disk.clone();
+        let change = match origin {
+            Origin::Change => {
+                streaks = Self::merge(&self.base, &self.streaks, on_disk.clone());
+                let change = Change::between(&on_disk, &streaks);
+                if let Some(change) = &change {
+                    history.push(change.clone());
+                }
+                change
+            }
+            Origin::Undo => history.undo().inspect(|change| change.reverse().apply(&mut streaks)),
+            Origin::Redo => history.redo().inspect(|change| change.apply(&mut streaks)),
+        };
+
+        let backfills: Vec<(Streak, Vec<NaiveDate>)> = std::mem::take(&mut self.backfills)
+            .into_iter()
+            .filter(|_| origin == Origin::Change)
+            .filter_map(|(id, dates)| Some((streaks.iter().find(|s| s.id == id)?.clone(), dates)))
+            .collect();
+        if let Some(journal) = &journal {
+            journal.record(&on_disk, &streaks, origin, &backfills)?;
+        }
+        let data = Self::write_streaks(&mut file, &streaks, self.cipher.as_ref())?;
+        if let Some(journal) = journal {
+            journal.wrote(&data)?;
+        }
+        if change.is_some() {
+            history.save(filename, self.cipher.as_ref())?;
+        }
+
+        self.base = streaks.clone();
+        self.streaks = streaks;
+        Ok(change)
+    }
+
+    pub fn save(&mut self) -> Result<(), std::io::Error> {
+        let filename = self.filename.clone();
+        self.save_database(filename.as_str(), Origin::Change)?;
+        Ok(())
+    }
+
+    /// Revert the most recent change. Unsaved changes are discarded.
+    pub fn undo(&mut self) -> Result<Option<Change>, std::io::Error> {
+        let filename = self.filename.clone();
+        self.save_database(filename.as_str(), Origin::Undo)
+    }
+
+    /// Re-apply the most recently undone change. Unsaved changes are discarded.
+    pub fn redo(&mut self) -> Result<Option<Change>, std::io::Error> {
+        let filename = self.filename.clone();
+        self.save_database(filename.as_str(), Origin::Redo)
+    }
+
+    pub fn history(&self) -> History {
+        History::load(&self.filename, self.cipher.as_ref())
+    }
+
+    /// Check whether a database file is encrypted and needs a passphrase to open
+    pub fn is_encrypted(filename: &str) -> bool {
+        std::fs::read(filename).is_ok_and(|data| crypto::is_encrypted(&data))
+    }
+
+    pub fn encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Rewrite the database and its history with a different cipher, or none
+    fn set_cipher(&mut self, cipher: Option<Cipher>) -> Result<(), std::io::Error> {
+        let filename = self.filename.clone();
+        Self::create_if_missing(&filename)?;
+        let mut file = OpenOptions::new().read(true).write(true).open(&filename)?;
+        Self::lock(&file, &filename, true)?;
+
+        let streaks = Self::read_streaks(&mut file, self.cipher.as_ref())?;
+        let history = History::load(&filename, self.cipher.as_ref());
+        self.cipher = cipher;
+        Self::write_streaks(&mut file, &streaks, self.cipher.as_ref())?;
+        history.save(&filename, self.cipher.as_ref())?;
+
+        self.base = streaks.clone();
+        self.streaks = streaks;
+        Ok(())
+    }
+
+    /// Encrypt the database file with a passphrase from now on
+    pub fn encrypt(&mut self, passphrase: &str) -> Result<(), std::io::Error> {
+        if self.journal().is_some() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "The journal can't be encrypted, turn it off first",
+            ));
+        }
+        self.set_cipher(Some(Cipher::new(passphrase)?))
+    }
+
+    /// Store the database file as plain text again
+    pub fn decrypt(&mut self) -> Result<(), std::io::Error> {
+        self.set_cipher(None)
+    }
+
+    /// Open another database file, unlocking it with this one's passphrase if it is encrypted
+    pub fn reopen(&self, filename: &str) -> Result<Self, std::io::Error> {
+        let passphrase = self.cipher.as_ref().map(|cipher| cipher.passphrase());
+        Self::open(filename, passphrase)
     }
 
-    pub fn save(&self) -> Result<(), std::io::Error> {
-        self.save_database(self.filename.as_str());
+    /// Re-read the database from disk, keeping any changes not yet saved
+    pub fn reload(&mut self) -> Result<(), std::io::Error> {
+        let on_disk = Self::load_database(self.filename.as_str(), self.cipher.as_ref())?;
+        self.streaks = Self::merge(&self.base, &self.streaks, on_disk.clone());
+        self.base = on_disk;
         Ok(())
     }
 
+    /// Start watching the database file for changes made by other processes.
+    ///
+    /// The containing directory is watched so files replaced by editors or
+    /// sync tools are still noticed.
+    pub fn watch(&self) -> notify::Result<DatabaseWatcher> {
+        let path = Path::new(&self.filename);
+        let file_name = path.file_name().unwrap_or_default().to_os_string();
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(DatabaseWatcher {
+            _watcher: watcher,
+            events,
+            file_name,
+        })
+    }
+
+    /// The event journal, if journal mode is turned on for this database
+    pub fn journal(&self) -> Option<Journal> {
+        Journal::open(&self.filename)
+    }
+
+    /// Turn on journal mode, starting the history from the current streaks
+    pub fn enable_journal(&self) -> Result<Journal, std::io::Error> {
+        if self.encrypted() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "The journal can't be used with an encrypted database",
+            ));
+        }
+        match self.journal() {
+            Some(journal) => Ok(journal),
+            None => Journal::create(&self.filename, &self.streaks),
+        }
+    }
+
+    /// Turn off journal mode, discarding the history
+    pub fn disable_journal(&self) -> Result<(), std::io::Error> {
+        match self.journal() {
+            Some(journal) => journal.remove(),
+            None => Ok(()),
+        }
+    }
+
     pub fn create_from_file(filename: &str, data: &str) -> Result<Self, std::io::Error> {
         let mut db = Self::new(filename)?;
-        let streaks: Vec<Streak> = ron::de::from_str(data).unwrap();
+        let streaks: Vec<Streak> = ron::de::from_str(data)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
         for streak in streaks {
             db.streaks.push(streak);
         }
         Ok(db)
     }
 
+    /// Merge in streaks from a diverged copy of the database, matching them
+    /// by id. Check-ins made in either copy are combined when both journals
+    /// hold every one of them; those from the other copy are backfilled into
+    /// ours with the next save.
+    pub fn merge_streaks(
+        &mut self,
+        others: Vec<Streak>,
+        their_history: Option<&CheckinHistory>,
+    ) -> Result<MergeSummary, std::io::Error> {
+        let our_history = CheckinHistory::load(self)?;
+        // The days a streak was checked in on, if the history has all of them
+        let all_dates = |history: Option<&CheckinHistory>, streak: &Streak| {
+            let dates = history.map(|h| h.dates(streak.id)).unwrap_or_default();
+            (dates.len() == streak.total_checkins as usize).then(|| dates.to_vec())
+        };
+        let mut summary = MergeSummary::default();
+        let mut backfills = vec![];
+        for other in others {
+            match self.streaks.iter_mut().find(|s| s.id == other.id) {
+                Some(existing) => {
+                    let mut merged = existing.merge(&other);
+                    let diverged = existing.total_checkins != other.total_checkins
+                        || existing.last_checkin != other.last_checkin;
+                    if diverged {
+                        let ours = all_dates(our_history.as_ref(), existing);
+                        match (ours, all_dates(their_history, &other)) {
+                            (Some(ours), Some(theirs)) => {
+                                let missing: Vec<NaiveDate> =
+                                    theirs.into_iter().filter(|d| !ours.contains(d)).collect();
+                                merged.count_checkins(&[ours, missing.clone()].concat());
+                                backfills.push((merged.id, missing));
+                            }
+                            _ => summary.conflicts.push(merged.clone()),
+                        }
+                    }
+                    if merged == *existing {
+                        summary.unchanged += 1;
+                    } else {
+                        *existing = merged.clone();
+                        summary.updated.push(merged);
+                    }
+                }
+                None => {
+                    if let Some(dates) = all_dates(their_history, &other) {
+                        backfills.push((other.id, dates));
+                    }
+                    summary.added.push(other.clone());
+                    self.streaks.push(other);
+                }
+            }
+        }
+        for (id, dates) in backfills {
+            self.backfill(id, &dates);
+        }
+        Ok(summary)
+    }
+
+    /// Note the days a streak was checked in on before it was kept here,
+    /// like ones imported from another tracker. They are recorded in the
+    /// journal, if it's on, with the next save.
+    pub fn backfill(&mut self, id: Uuid, dates: &[NaiveDate]) {
+        if !dates.is_empty() {
+            self.backfills.push((id, dates.to_vec()));
+        }
+    }
+
     pub fn add(&mut self, streak: Streak) -> Result<(), std::io::Error> {
         let mut streaks = self.streaks.clone();
         streaks.push(streak);
@@ -96,8 +502,9 @@ impl Database {
         Ok(())
     }
 
-    pub fn update(&mut self, id: Uuid, streak: Streak) -> Result<(), std::io::Error> {
+    pub fn update(&mut self, id: Uuid, mut streak: Streak) -> Result<(), std::io::Error> {
         self.delete(id)?;
+        streak.modified_at = Some(Local::now());
         let mut streaks = self.streaks.clone();
         streaks.push(streak);
         self.streaks = streaks;
@@ -116,11 +523,25 @@ impl Database {
     }
 
     pub fn new(filename: &str) -> Result<Self, std::io::Error> {
+        Self::open(filename, None)
+    }
+
+    /// Open a database, using the passphrase if the file is encrypted
+    pub fn open(filename: &str, passphrase: Option<&str>) -> Result<Self, std::io::Error> {
         Self::create_if_missing(filename)?;
-        let existing_db = Self::load_database(filename)?;
+        let cipher = match passphrase {
+            Some(passphrase) if Self::is_encrypted(filename) => {
+                Some(Cipher::for_data(passphrase, &std::fs::read(filename)?)?)
+            }
+            _ => None,
+        };
+        let existing_db = Self::load_database(filename, cipher.as_ref())?;
         let new_db = Self {
             streaks: existing_db.clone(),
             filename: filename.to_string(),
+            base: existing_db,
+            cipher,
+            backfills: vec![],
         };
         Ok(new_db)
     }
@@ -170,15 +591,77 @@ impl Database {
         }
     }
 
+    /// The streak an ident refers to, if it refers to exactly one
     pub fn get_by_id(&mut self, ident: &str) -> Option<Streak> {
-        let streak = self
+        let mut matches = self.find_by_ident(ident);
+        match matches.len() {
+            1 => Some(matches.remove(0)),
+            _ => None,
+        }
+    }
+
+    /// The streaks an ident could refer to. Like git, an ident can be any
+    /// prefix of an id at least `MIN_IDENT_LEN` long; failing that it is
+    /// matched against task names, exactly, then as part of a name, then
+    /// as letters appearing in order. The first of these to match anything
+    /// wins, so more than one result means the ident is ambiguous.
+    pub fn find_by_ident(&self, ident: &str) -> Vec<Streak> {
+        self.match_ident(ident, true)
+    }
+
+    /// Like `find_by_ident`, but only by id or the whole task name, for
+    /// commands that shouldn't act on a near miss
+    pub fn find_by_exact_ident(&self, ident: &str) -> Vec<Streak> {
+        self.match_ident(ident, false)
+    }
+
+    fn match_ident(&self, ident: &str, loose: bool) -> Vec<Streak> {
+        let ident = ident.trim().to_lowercase();
+        if ident.is_empty() {
+            return vec![];
+        }
+        let is_subsequence = |task: &str| {
+            let mut chars = task.chars();
+            ident.chars().all(|c| chars.any(|t| t == c))
+        };
+        let tiers: [&dyn Fn(&Streak) -> bool; 4] = [
+            &|s| ident.len() >= MIN_IDENT_LEN && s.id.to_string().starts_with(&ident),
+            &|s| s.task.to_lowercase() == ident,
+            &|s| s.task.to_lowercase().contains(&ident),
+            &|s| is_subsequence(&s.task.to_lowercase()),
+        ];
+        let tiers = match loose {
+            true => &tiers[..],
+            false => &tiers[..2],
+        };
+        for matches_tier in tiers {
+            let matches: Vec<Streak> = self
+                .streaks
+                .iter()
+                .filter(|s| matches_tier(s))
+                .cloned()
+                .collect();
+            if !matches.is_empty() {
+                return matches;
+            }
+        }
+        vec![]
+    }
+
+    /// The shortest start of `id`, at least `MIN_IDENT_LEN` characters long,
+    /// that no other streak's id shares, so it works as an ident
+    pub fn short_id(&self, id: Uuid) -> String {
+        let id = id.to_string();
+        let others: Vec<String> = self
             .streaks
             .iter()
-            .find(|s| s.id.to_string()[0..5].to_string() == ident);
-        match streak {
-            Some(streak) => Some(streak.clone()),
-            None => None,
-        }
+            .map(|s| s.id.to_string())
+            .filter(|other| *other != id)
+            .collect();
+        let len = (MIN_IDENT_LEN..id.len())
+            .find(|&len| !others.iter().any(|other| other.starts_with(&id[..len])))
+            .unwrap_or(id.len());
+        id[..len].to_string()
     }
 
     pub fn search(&mut self, query: &str) -> Vec<Streak> {
@@ -212,6 +695,9 @@ impl Default for Database {
         Self {
             streaks: Vec::<Streak>::new(),
             filename: "skidmarks.ron".to_string(),
+            base: Vec::<Streak>::new(),
+            cipher: None,
+            backfills: vec![],
         }
     }
 }
@@ -266,7 +752,7 @@ mod tests {
         let db_file = temp.child("test_load_database_empty.ron");
         let _ = Database::new(db_file.to_str().unwrap()).unwrap();
 
-        let result = Database::load_database(db_file.to_str().unwrap());
+        let result = Database::load_database(db_file.to_str().unwrap(), None);
         assert!(result.is_ok());
         assert!(result.unwrap().is_empty());
 
@@ -285,13 +771,14 @@ mod tests {
         db.save().unwrap();
 
         let expected_content = format!(
-            r#"[(id:"{}",task:"{}",frequency:Daily,last_checkin:{:?},current_streak:{},longest_streak:{},total_checkins:{})]"#,
+            r#"[(id:"{}",task:"{}",frequency:Daily,last_checkin:{:?},current_streak:{},longest_streak:{},total_checkins:{},modified_at:{})]"#,
             streak.id,
             streak.task,
             streak.last_checkin,
             streak.current_streak,
             streak.longest_streak,
-            streak.total_checkins
+            streak.total_checkins,
+            ron::ser::to_string(&streak.modified_at).unwrap()
         );
 
         let result = std::fs::read_to_string(file_path);
@@ -300,6 +787,411 @@ mod tests {
         temp.close().unwrap();
     }
 
+    #[test]
+    fn save_merges_concurrent_changes() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_save_merges_concurrent_changes.ron");
+        let file_path = db_file.to_str().unwrap();
+
+        let mut db = Database::new(file_path).unwrap();
+        let streak = Streak::new_daily("brush teeth".to_string());
+        db.add(streak.clone()).unwrap();
+        db.save().unwrap();
+
+        let mut first = Database::new(file_path).unwrap();
+        let mut second = Database::new(file_path).unwrap();
+
+        first.checkin(streak.id).unwrap();
+        first.save().unwrap();
+
+        let floss = Streak::new_daily("floss".to_string());
+        second.add(floss.clone()).unwrap();
+        second.save().unwrap();
+
+        let mut result = Database::new(file_path).unwrap();
+        assert_eq!(result.num_tasks(), 2);
+        assert_eq!(result.get_one(streak.id).unwrap().total_checkins, 1);
+        assert_eq!(result.get_one(floss.id).unwrap(), floss);
+        assert_eq!(second.streaks, result.streaks);
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn save_merges_concurrent_delete() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_save_merges_concurrent_delete.ron");
+        let file_path = db_file.to_str().unwrap();
+
+        let mut db = Database::create_from_file(file_path, DATABASE_PRELOAD).unwrap();
+        db.save().unwrap();
+        let removed = db.streaks[0].clone();
+
+        let mut first = Database::new(file_path).unwrap();
+        let mut second = Database::new(file_path).unwrap();
+
+        first.delete(removed.id).unwrap();
+        first.save().unwrap();
+
+        second.checkin(second.streaks[1].id).unwrap();
+        second.save().unwrap();
+
+        let mut result = Database::new(file_path).unwrap();
+        assert_eq!(result.num_tasks(), 2);
+        assert!(result.get_one(removed.id).is_none());
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn save_fails_while_locked() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_save_fails_while_locked.ron");
+        let file_path = db_file.to_str().unwrap();
+
+        let mut db = Database::new(file_path).unwrap();
+        db.add(Streak::new_daily("brush teeth".to_string())).unwrap();
+
+        let other = File::open(file_path).unwrap();
+        FileExt::lock_exclusive(&other).unwrap();
+
+        let result = db.save();
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::WouldBlock);
+
+        FileExt::unlock(&other).unwrap();
+        assert!(db.save().is_ok());
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn save_fails_over_corrupt_file() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_save_fails_over_corrupt_file.ron");
+        let file_path = db_file.to_str().unwrap();
+
+        let mut db = Database::create_from_file(file_path, DATABASE_PRELOAD).unwrap();
+        let corrupt = &DATABASE_PRELOAD[..DATABASE_PRELOAD.len() / 2];
+        db_file.write_str(corrupt).unwrap();
+
+        db.checkin(db.streaks[0].id).unwrap();
+        let result = db.save();
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(std::fs::read_to_string(file_path).unwrap(), corrupt);
+        assert!(Database::new(file_path).is_err());
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn reload_picks_up_external_changes() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_reload.ron");
+        let file_path = db_file.to_str().unwrap();
+
+        let mut db = Database::new(file_path).unwrap();
+        let mut other = Database::new(file_path).unwrap();
+        let streak = Streak::new_daily("brush teeth".to_string());
+        other.add(streak.clone()).unwrap();
+        other.save().unwrap();
+        assert_eq!(db.num_tasks(), 0);
+
+        db.reload().unwrap();
+        assert_eq!(db.get_one(streak.id).unwrap(), streak);
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn watch_reports_changes() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_watch.ron");
+        let file_path = db_file.to_str().unwrap();
+
+        let db = Database::new(file_path).unwrap();
+        let watcher = db.watch().unwrap();
+        assert!(!watcher.changed());
+
+        let mut other = Database::new(file_path).unwrap();
+        other.add(Streak::new_daily("floss".to_string())).unwrap();
+        other.save().unwrap();
+
+        let changed = (0..50).any(|_| {
+            sleep(Duration::from_millis(20));
+            watcher.changed()
+        });
+        assert!(changed);
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn journal_is_source_of_truth() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_journal_is_source_of_truth.ron");
+        let file_path = db_file.to_str().unwrap();
+
+        let mut db = Database::new(file_path).unwrap();
+        db.enable_journal().unwrap();
+        let streak = Streak::new_daily("brush teeth".to_string());
+        db.add(streak.clone()).unwrap();
+        db.save().unwrap();
+        let stale = std::fs::read(file_path).unwrap();
+        db.checkin(streak.id).unwrap();
+        db.save().unwrap();
+
+        let events = db.journal().unwrap().events().unwrap();
+        assert_eq!(events.len(), 2);
+
+        // As if skidmarks stopped after logging the check-in but before
+        // writing the file
+        std::fs::write(file_path, &stale).unwrap();
+        db.journal().unwrap().wrote(&stale).unwrap();
+        let mut result = Database::new(file_path).unwrap();
+        assert_eq!(result.get_one(streak.id).unwrap().total_checkins, 1);
+
+        db.disable_journal().unwrap();
+        assert!(db.journal().is_none());
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn journal_keeps_edits_to_the_file() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_journal_keeps_edits_to_the_file.ron");
+        let file_path = db_file.to_str().unwrap();
+
+        let mut db = Database::new(file_path).unwrap();
+        db.enable_journal().unwrap();
+        let mut streak = Streak::new_daily("brush teeth".to_string());
+        db.add(streak.clone()).unwrap();
+        db.save().unwrap();
+
+        streak.task = "brush and floss".to_string();
+        let synced = Streak::new_weekly("read".to_string());
+        let edited = ron::ser::to_string(&vec![streak.clone(), synced.clone()]).unwrap();
+        std::fs::write(file_path, edited).unwrap();
+
+        let mut db = Database::new(file_path).unwrap();
+        assert_eq!(db.get_one(streak.id).unwrap().task, "brush and floss");
+        assert!(db.get_one(synced.id).is_some());
+
+        db.add(Streak::new_daily("walk".to_string())).unwrap();
+        db.save().unwrap();
+        let actions: Vec<String> = db
+            .journal()
+            .unwrap()
+            .events()
+            .unwrap()
+            .iter()
+            .map(|event| event.action.to_string())
+            .collect();
+        assert_eq!(actions, vec!["added", "edited", "added", "added"]);
+        assert_eq!(Database::new(file_path).unwrap().num_tasks(), 3);
+
+        // Lowered counters and removed streaks are taken as they are
+        db.checkin(streak.id).unwrap();
+        db.save().unwrap();
+        let edited = ron::ser::to_string(&vec![streak.clone()]).unwrap();
+        std::fs::write(file_path, edited).unwrap();
+        let mut db = Database::new(file_path).unwrap();
+        assert_eq!(db.get_one(streak.id).unwrap().total_checkins, 0);
+        db.save().unwrap();
+        let journal = db.journal().unwrap();
+        assert_eq!(journal.replay().unwrap(), vec![streak]);
+        let events = journal.events().unwrap();
+        let actions: Vec<String> = events[5..]
+            .iter()
+            .map(|event| event.action.to_string())
+            .collect();
+        assert_eq!(actions, vec!["deleted", "deleted", "edited"]);
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_undo_and_redo.ron");
+        let file_path = db_file.to_str().unwrap();
+
+        let mut db = Database::new(file_path).unwrap();
+        db.enable_journal().unwrap();
+        let streak = Streak::new_daily("brush teeth".to_string());
+        db.add(streak.clone()).unwrap();
+        db.save().unwrap();
+        db.delete(streak.id).unwrap();
+        db.save().unwrap();
+        assert!(db.get_all().is_empty());
+
+        let undone = db.undo().unwrap().unwrap();
+        assert_eq!(undone.before, vec![streak.clone()]);
+        assert_eq!(db.get_all(), vec![streak.clone()]);
+        assert_eq!(Database::new(file_path).unwrap().get_all(), vec![streak.clone()]);
+
+        db.undo().unwrap();
+        assert!(db.get_all().is_empty());
+        assert!(db.undo().unwrap().is_none());
+
+        db.redo().unwrap();
+        assert_eq!(db.get_all(), vec![streak.clone()]);
+        assert!(db.history().can_redo());
+
+        let events = db.journal().unwrap().events().unwrap();
+        assert_eq!(events.len(), 5);
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn merge_diverged_copies() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let ours_file = temp.child("test_merge_ours.ron");
+        let theirs_file = temp.child("test_merge_theirs.ron");
+
+        let mut ours =
+            Database::create_from_file(ours_file.to_str().unwrap(), DATABASE_PRELOAD).unwrap();
+        let mut theirs =
+            Database::create_from_file(theirs_file.to_str().unwrap(), DATABASE_PRELOAD).unwrap();
+        theirs.checkin(theirs.streaks[0].id).unwrap();
+        theirs.add(Streak::new_daily("Stretch".to_string())).unwrap();
+        ours.checkin(ours.streaks[1].id).unwrap();
+
+        let summary = ours.merge_streaks(theirs.streaks.clone(), None).unwrap();
+        assert_eq!(summary.added.len(), 1);
+        assert_eq!(summary.updated.len(), 1);
+        assert_eq!(summary.unchanged, 2);
+        assert_eq!(summary.conflicts.len(), 2);
+        assert_eq!(ours.num_tasks(), 4);
+        assert_eq!(ours.streaks[0].total_checkins, 3);
+        assert_eq!(ours.streaks[1].total_checkins, 2);
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn merge_combines_checkins_from_journals() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2024, 8, d).unwrap();
+        let copy = |name: &str, dates: &[NaiveDate], journal: bool| {
+            let db_file = temp.child(name);
+            let mut db = Database::new(db_file.to_str().unwrap()).unwrap();
+            if journal {
+                db.enable_journal().unwrap();
+            }
+            let mut streak = Streak::new_daily("Walk".to_string());
+            streak.id = Uuid::parse_str("77cbbb3f-2690-45a9-9a30-94a53556d93e").unwrap();
+            streak.count_checkins(dates);
+            db.add(streak.clone()).unwrap();
+            db.backfill(streak.id, dates);
+            db.save().unwrap();
+            db
+        };
+        let mut ours = copy("test_merge_journal_ours.ron", &[day(1), day(3)], true);
+        let theirs = copy("test_merge_journal_theirs.ron", &[day(1), day(2)], true);
+
+        let history = CheckinHistory::load(&theirs).unwrap();
+        let summary = ours
+            .merge_streaks(theirs.streaks.clone(), history.as_ref())
+            .unwrap();
+        assert_eq!(summary.updated.len(), 1);
+        assert!(summary.conflicts.is_empty());
+        let merged = ours.streaks[0].clone();
+        assert_eq!(merged.total_checkins, 3);
+        assert_eq!(merged.current_streak, 3);
+        assert_eq!(merged.longest_streak, 3);
+        assert_eq!(merged.last_checkin, Some(day(3)));
+
+        ours.save().unwrap();
+        let history = CheckinHistory::load(&ours).unwrap().unwrap();
+        assert_eq!(history.dates(merged.id), &[day(1), day(2), day(3)]);
+        let events = ours.journal().unwrap().events().unwrap();
+        assert_eq!(crate::stats::checkins(&events).len(), 3);
+
+        let plain = copy("test_merge_journal_plain.ron", &[day(4)], false);
+        let summary = ours.merge_streaks(plain.streaks.clone(), None).unwrap();
+        assert_eq!(summary.conflicts.len(), 1);
+        assert_eq!(ours.streaks[0].total_checkins, 3);
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn find_by_ident() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_find_by_ident.ron");
+        let mut db = Database::create_from_file(db_file.to_str().unwrap(), DATABASE_PRELOAD).unwrap();
+        let mut twin = db.streaks[0].clone();
+        twin.id = Uuid::parse_str("00e8a16c-1111-4e90-8c3f-2ee7aa6a2210").unwrap();
+        twin.task = "Poop again".to_string();
+        db.add(twin).unwrap();
+
+        let tasks = |ident: &str| -> Vec<String> {
+            db.find_by_ident(ident).into_iter().map(|s| s.task).collect()
+        };
+        assert_eq!(tasks("77cbbb3f"), vec!["Take a walk"]);
+        assert_eq!(tasks("77CB"), vec!["Take a walk"]);
+        assert_eq!(tasks("00e8a16c"), vec!["Poop", "Poop again"]);
+        assert_eq!(tasks("00e8a16c-0"), vec!["Poop"]);
+        assert_eq!(tasks("take a walk"), vec!["Take a walk"]);
+        assert_eq!(tasks("poop"), vec!["Poop"]);
+        assert_eq!(tasks("brekk"), vec!["Eat brekkie"]);
+        assert_eq!(tasks("twlk"), vec!["Take a walk"]);
+        assert!(tasks("zzz").is_empty());
+        assert_eq!(db.get_by_id("00e8a"), None);
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn short_id() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_short_id.ron");
+        let mut db = Database::create_from_file(db_file.to_str().unwrap(), DATABASE_PRELOAD).unwrap();
+        let mut twin = db.streaks[0].clone();
+        twin.id = Uuid::parse_str("00e8a16c-1111-4e90-8c3f-2ee7aa6a2210").unwrap();
+        db.add(twin.clone()).unwrap();
+
+        assert_eq!(db.short_id(db.streaks[1].id), "77cb");
+        assert_eq!(db.short_id(db.streaks[0].id), "00e8a16c-0");
+        assert_eq!(db.short_id(twin.id), "00e8a16c-1");
+        for streak in &db.streaks {
+            let short = db.short_id(streak.id);
+            assert_eq!(db.find_by_ident(&short), vec![streak.clone()]);
+        }
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn encrypt_and_decrypt() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_encrypt_and_decrypt.ron");
+        let file_path = db_file.to_str().unwrap();
+
+        let mut db = Database::create_from_file(file_path, DATABASE_PRELOAD).unwrap();
+        db.save().unwrap();
+        db.encrypt("correct horse").unwrap();
+        assert!(Database::is_encrypted(file_path));
+        assert!(!std::fs::read(file_path).unwrap().windows(4).any(|w| w == b"Poop"));
+
+        db.checkin(db.streaks[0].id).unwrap();
+        db.save().unwrap();
+
+        assert!(Database::new(file_path).is_err());
+        assert!(Database::open(file_path, Some("battery staple")).is_err());
+        let mut db = Database::open(file_path, Some("correct horse")).unwrap();
+        assert_eq!(db.num_tasks(), 3);
+        assert!(db.history().can_undo());
+
+        db.decrypt().unwrap();
+        assert!(!Database::is_encrypted(file_path));
+        assert_eq!(Database::new(file_path).unwrap().num_tasks(), 3);
+
+        temp.close().unwrap();
+    }
+
     #[test]
     fn add_streak() {
         let temp = assert_fs::TempDir::new().unwrap();
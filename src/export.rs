@@ -0,0 +1,242 @@
+use chrono::{DateTime, Local, NaiveDate};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::Database;
+use crate::stats::checkins;
+use crate::streak::Streak;
+
+/// Version of the export layout, bumped whenever a field is renamed or removed
+pub const EXPORT_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+    Ron,
+}
+
+/// A streak as it appears in exports. The field names are a stable contract.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StreakRecord {
+    pub id: Uuid,
+    pub task: String,
+    /// `daily` or `weekly`
+    pub frequency: String,
+    /// `waiting`, `done` or `missed`, as of the export
+    pub status: String,
+    pub last_checkin: Option<NaiveDate>,
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    pub total_checkins: u32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub archived: bool,
+}
+
+impl From<&Streak> for StreakRecord {
+    fn from(streak: &Streak) -> Self {
+        Self {
+            id: streak.id,
+            task: streak.task.clone(),
+            frequency: streak.frequency.to_string(),
+            status: streak.status().to_string(),
+            last_checkin: streak.last_checkin,
+            current_streak: streak.current_streak,
+            longest_streak: streak.longest_streak,
+            total_checkins: streak.total_checkins,
+            tags: streak.tags.clone(),
+            archived: streak.archived,
+        }
+    }
+}
+
+/// A check-in recorded by the journal
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CheckinRecord {
+    pub streak_id: Uuid,
+    pub task: String,
+    /// The day it counts for, missing from exports made before it was added
+    #[serde(default)]
+    pub date: Option<NaiveDate>,
+    pub checked_in_at: DateTime<Local>,
+}
+
+/// Everything that gets exported. Check-ins are only known while the journal
+/// is on; skidmarks doesn't store notes, so there are none to export.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Export {
+    pub version: u32,
+    pub streaks: Vec<StreakRecord>,
+    pub checkins: Vec<CheckinRecord>,
+}
+
+/// One line of a CSV export. Streaks and check-ins share the table and are
+/// told apart by `kind`; columns that don't apply to a row are left empty.
+#[derive(Debug, Default, Serialize)]
+struct CsvRow {
+    kind: &'static str,
+    id: Option<Uuid>,
+    task: String,
+    frequency: Option<String>,
+    status: Option<String>,
+    last_checkin: Option<NaiveDate>,
+    current_streak: Option<u32>,
+    longest_streak: Option<u32>,
+    total_checkins: Option<u32>,
+    /// Separated by `;`
+    tags: Option<String>,
+    date: Option<NaiveDate>,
+    checked_in_at: Option<DateTime<Local>>,
+}
+
+impl Export {
+    pub fn new(db: &Database) -> Result<Self, std::io::Error> {
+        let events = match db.journal() {
+            Some(journal) => journal.events()?,
+            None => vec![],
+        };
+        let checkins = checkins(&events)
+            .into_iter()
+            .map(|checkin| CheckinRecord {
+                streak_id: checkin.streak_id,
+                task: checkin.task,
+                date: Some(checkin.date),
+                checked_in_at: checkin.at,
+            })
+            .collect();
+
+        Ok(Self {
+            version: EXPORT_VERSION,
+            streaks: db.streaks.iter().map(StreakRecord::from).collect(),
+            checkins,
+        })
+    }
+
+    fn csv_rows(&self) -> Vec<CsvRow> {
+        let streaks = self.streaks.iter().map(|streak| CsvRow {
+            kind: "streak",
+            id: Some(streak.id),
+            task: streak.task.clone(),
+            frequency: Some(streak.frequency.clone()),
+            status: Some(streak.status.clone()),
+            last_checkin: streak.last_checkin,
+            current_streak: Some(streak.current_streak),
+            longest_streak: Some(streak.longest_streak),
+            total_checkins: Some(streak.total_checkins),
+            tags: Some(streak.tags.join(";")),
+            ..Default::default()
+        });
+        let checkins = self.checkins.iter().map(|checkin| CsvRow {
+            kind: "checkin",
+            id: Some(checkin.streak_id),
+            task: checkin.task.clone(),
+            date: checkin.date,
+            checked_in_at: Some(checkin.checked_in_at),
+            ..Default::default()
+        });
+        streaks.chain(checkins).collect()
+    }
+
+    pub fn render(&self, format: ExportFormat) -> Result<String, std::io::Error> {
+        let invalid = |err: String| std::io::Error::new(std::io::ErrorKind::InvalidData, err);
+        match format {
+            ExportFormat::Json => {
+                serde_json::to_string_pretty(self).map_err(|err| invalid(err.to_string()))
+            }
+            ExportFormat::Ron => {
+                ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+                    .map_err(|err| invalid(err.to_string()))
+            }
+            ExportFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(vec![]);
+                for row in self.csv_rows() {
+                    writer
+                        .serialize(row)
+                        .map_err(|err| invalid(err.to_string()))?;
+                }
+                let data = writer
+                    .into_inner()
+                    .map_err(|err| invalid(err.to_string()))?;
+                String::from_utf8(data).map_err(|err| invalid(err.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn export_formats() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_export_formats.ron");
+        let mut db = Database::new(db_file.to_str().unwrap()).unwrap();
+        db.add(Streak::new_daily("Poop, daily".to_string()))
+            .unwrap();
+        db.save().unwrap();
+        db.enable_journal().unwrap();
+        db.checkin(db.streaks[0].id).unwrap();
+        db.save().unwrap();
+
+        let export = Export::new(&db).unwrap();
+        assert_eq!(export.streaks.len(), 1);
+        assert_eq!(export.checkins.len(), 1);
+        assert_eq!(export.checkins[0].date, Some(Config::current().today()));
+
+        let json = export.render(ExportFormat::Json).unwrap();
+        let parsed: Export = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, export);
+        assert!(json.contains("\"total_checkins\": 1"));
+
+        let ron = export.render(ExportFormat::Ron).unwrap();
+        assert_eq!(ron::de::from_str::<Export>(&ron).unwrap(), export);
+
+        let csv = export.render(ExportFormat::Csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "kind,id,task,frequency,status,last_checkin,current_streak,longest_streak,total_checkins,tags,date,checked_in_at"
+        );
+        assert!(lines[1].starts_with("streak,"));
+        assert!(lines[1].contains("\"Poop, daily\",daily,done,"));
+        assert!(lines[2].starts_with("checkin,"));
+        assert_eq!(lines.len(), 3);
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn export_leaves_out_undone_checkins() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_export_leaves_out_undone_checkins.ron");
+        let mut db = Database::new(db_file.to_str().unwrap()).unwrap();
+        db.enable_journal().unwrap();
+        db.add(Streak::new_daily("Poop".to_string())).unwrap();
+        db.add(Streak::new_daily("Floss".to_string())).unwrap();
+        db.save().unwrap();
+        db.checkin(db.streaks[0].id).unwrap();
+        db.save().unwrap();
+        db.checkin(db.streaks[1].id).unwrap();
+        db.save().unwrap();
+
+        db.undo().unwrap();
+        let tasks = |db: &Database| -> Vec<String> {
+            let export = Export::new(db).unwrap();
+            export.checkins.into_iter().map(|c| c.task).collect()
+        };
+        assert_eq!(tasks(&db), vec!["Poop"]);
+
+        db.redo().unwrap();
+        assert_eq!(tasks(&db), vec!["Poop", "Floss"]);
+
+        temp.close().unwrap();
+    }
+}
@@ -1,6 +1,8 @@
+use serde::{Deserialize, Serialize};
+
 use crate::streak::{Status, Streak};
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum FilterByStatus {
     All,
     Done,
@@ -1,22 +1,33 @@
-use crate::cli::get_database_url;
 use crate::color::GuiStyles;
+use crate::config;
 use crate::filtering::FilterByStatus;
+use crate::profile::Profiles;
 use crate::sorting::{SortByDirection, SortByField};
 use crate::streak::Status;
-use crate::{db::Database, streak::Frequency, streak::Streak};
+use crate::{db::Database, db::DatabaseWatcher, streak::Frequency, streak::Streak};
 use dioxus::desktop::{use_global_shortcut, Config, WindowBuilder};
 use dioxus::prelude::*;
 use std::collections::HashMap;
+use std::time::Duration;
 use uuid::Uuid;
 
-pub fn main() {
+/// Database and profile picked on the command line, shared with the app through its context
+#[derive(Clone, Debug)]
+struct StartupOptions {
+    db: Database,
+    profile: Option<String>,
+}
+
+pub fn main(db: Database, profile: Option<String>) {
     LaunchBuilder::desktop()
         .with_cfg(Config::new().with_window(WindowBuilder::new().with_resizable(true)))
+        .with_context(StartupOptions { db, profile })
         .launch(app)
 }
 
 fn app() -> Element {
-    let mut streaks = use_signal(Streaks::new);
+    let options = use_context::<StartupOptions>();
+    let mut streaks = use_signal(move || Streaks::new(options.db.clone(), options.profile.clone()));
     let gui_styles = GuiStyles::new();
 
     let show_popup = use_signal(|| None);
@@ -26,6 +37,20 @@ fn app() -> Element {
     _ = use_global_shortcut("CmdOrCtrl+R", move || {
         streaks.write().refresh();
     });
+    // Pick up changes made by other processes
+    use_future(move || async move {
+        let mut watched: Option<(String, DatabaseWatcher)> = None;
+        loop {
+            let filename = streaks.read().db.filename.clone();
+            if watched.as_ref().map(|(name, _)| name) != Some(&filename) {
+                watched = streaks.read().db.watch().ok().map(|watcher| (filename, watcher));
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            if watched.as_ref().is_some_and(|(_, watcher)| watcher.changed()) {
+                streaks.write().refresh();
+            }
+        }
+    });
 
     rsx! {
         head::Link {
@@ -55,7 +80,9 @@ fn app() -> Element {
                     class: "is-size-1 has-text-centered has-text-weight-bold",
                     "Skidmarks"
                 }
+                {profile_switcher(streaks)}
             }
+            {error_message(streaks)}
             div { class: "section p-2 mt-2", {streak_search(streaks)} }
             main { class: "section p-2 mt-1 container", {streak_table(streaks, show_popup)} }
             div { class: "section p-2 mt-1", {streak_form(streaks)} }
@@ -65,6 +92,54 @@ fn app() -> Element {
     }
 }
 
+/// The last thing that went wrong, like the database being locked by
+/// another skidmarks, until it's dismissed or a later save works
+fn error_message(mut streaks: Signal<Streaks>) -> Element {
+    let Some(error) = streaks.read().error.clone() else {
+        return rsx! {};
+    };
+
+    rsx! {
+        div { class: "notification is-danger m-2",
+            button {
+                class: "delete",
+                onclick: move |_| {
+                    streaks.write().error = None;
+                }
+            }
+            "{error}"
+        }
+    }
+}
+
+fn profile_switcher(mut streaks: Signal<Streaks>) -> Element {
+    let profiles = Profiles::new().list();
+    let current = streaks.read().profile.clone();
+
+    rsx! {
+        div { class: "has-text-centered pb-2",
+            div { class: "select is-small",
+                select {
+                    name: "profile",
+                    oninput: move |event| {
+                        streaks.write().switch_profile(event.data().value());
+                    },
+                    if current.is_none() {
+                        option { selected: true, disabled: true, "Custom database" }
+                    }
+                    for name in profiles {
+                        option {
+                            selected: current.as_ref() == Some(&name),
+                            value: "{name}",
+                            "{name}"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
 fn streak_search(mut streaks: Signal<Streaks>) -> Element {
     rsx! {
         form { class: "form columns is-1 is-0-mobile",
@@ -89,19 +164,39 @@ fn streak_search(mut streaks: Signal<Streaks>) -> Element {
                             streaks.write().filter_by(filter);
                             streaks.write().load_streaks();
                         },
-                        option { "All" }
-                        option { "Done" }
-                        option { "Waiting" }
-                        option { "Missed" }
+                        option { selected: streaks.read().filter_by == FilterByStatus::All, "All" }
+                        option { selected: streaks.read().filter_by == FilterByStatus::Done, "Done" }
+                        option { selected: streaks.read().filter_by == FilterByStatus::Waiting, "Waiting" }
+                        option { selected: streaks.read().filter_by == FilterByStatus::Missed, "Missed" }
                     }
                 }
                 button {
                     class: "button",
                     onclick: move |_| {
-                        streaks.write().load_streaks();
+                        streaks.write().search(String::new());
                     },
                     "Reset"
                 }
+                div { class: "buttons has-addons is-inline-flex ml-2",
+                    button {
+                        class: "button",
+                        r#type: "button",
+                        disabled: !streaks.read().can_undo,
+                        onclick: move |_| {
+                            streaks.write().undo();
+                        },
+                        "↶ Undo"
+                    }
+                    button {
+                        class: "button",
+                        r#type: "button",
+                        disabled: !streaks.read().can_redo,
+                        onclick: move |_| {
+                            streaks.write().redo();
+                        },
+                        "↷ Redo"
+                    }
+                }
             }
         }
     }
@@ -179,7 +274,7 @@ fn streak_table(mut streaks: Signal<Streaks>, mut show_popup: Signal<Option<Uuid
                     let frequency = &streak.frequency.to_string();
                     let emoji = &streak.emoji_status();
                     let check_in = match &streak.last_checkin {
-                        Some(date) => date.to_string(),
+                        Some(date) => date.format(config::Config::current().date_format()).to_string(),
                         None => "None".to_string(),
                     };
                     
@@ -323,7 +418,7 @@ fn popup(mut is_open: Signal<Option<Uuid>>, mut streaks: Signal<Streaks>) -> Ele
                         div { class: "column",
                             h3 { "Last Checkin" }
                             p {
-                                {streak.as_ref().map_or("None".to_string(), |s| s.last_checkin.unwrap().to_string())}
+                                {streak.as_ref().map_or("None".to_string(), |s| s.last_checkin.unwrap().format(config::Config::current().date_format()).to_string())}
                             }
                         }
                         div { class: "column",
@@ -373,62 +468,125 @@ struct Streaks {
     sort_by: SortByField,
     sort_dir: SortByDirection,
     filter_by: FilterByStatus,
+    search: String,
+    profile: Option<String>,
+    /// Whether the undo history has anything to undo or redo, kept here so
+    /// rendering doesn't read the history file
+    can_undo: bool,
+    can_redo: bool,
+    /// Shown at the top of the window
+    error: Option<String>,
 }
 
 impl Streaks {
-    fn new() -> Self {
-        let db = Database::new(&get_database_url()).expect("Failed to connect to database");
+    fn new(db: Database, profile: Option<String>) -> Self {
+        let config = config::Config::current();
+        let (sort_by, sort_dir) = config.sort_order();
         let mut streaks = Self {
             db,
             streak_list: vec![],
-            sort_by: SortByField::Task,
-            sort_dir: SortByDirection::Ascending,
-            filter_by: FilterByStatus::All,
+            sort_by,
+            sort_dir,
+            filter_by: config.filter(),
+            search: String::new(),
+            profile,
+            can_undo: false,
+            can_redo: false,
+            error: None,
         };
 
         streaks.load_streaks();
+        streaks.load_history();
         streaks
     }
 
+    fn load_history(&mut self) {
+        let history = self.db.history();
+        self.can_undo = history.can_undo();
+        self.can_redo = history.can_redo();
+    }
+
     fn load_streaks(&mut self) {
         let sort_by = self.sort_by.clone();
         let sort_dir = self.sort_dir.clone();
         let filter_by = self.filter_by.clone();
         let streaks = self.db.get_sorted(sort_by, sort_dir);
+        let search = self.search.to_lowercase();
         let filtered_streaks = streaks
             .into_iter()
+            .filter(|streak| !streak.archived)
             .filter(|streak| match filter_by {
                 FilterByStatus::All => true,
                 FilterByStatus::Done => streak.status() == Status::Done,
                 FilterByStatus::Missed => streak.status() == Status::Missed,
                 FilterByStatus::Waiting => streak.status() == Status::Waiting,
             })
+            .filter(|streak| streak.task.to_lowercase().contains(&search))
             .collect();
         self.streak_list = filtered_streaks;
     }
 
+    fn fail(&mut self, message: &str, e: std::io::Error) {
+        self.error = Some(format!("{message}: {e}"));
+    }
+
     fn refresh(&mut self) {
-        let mut streak_signal = use_signal(Streaks::new);
-        streak_signal.write().load_streaks();
+        if let Err(e) = self.db.reload() {
+            self.fail("Failed to reload streaks", e);
+        }
+        self.load_streaks();
+        self.load_history();
+    }
+
+    fn save(&mut self) {
+        match self.db.save() {
+            Ok(_) => self.error = None,
+            Err(e) => self.fail("Failed to save streaks", e),
+        }
+        self.load_streaks();
+        self.load_history();
     }
 
     fn delete(&mut self, id: &Uuid) {
         match self.db.delete(*id) {
-            Ok(_) => {
-                let _ = self.db.save();
-                self.load_streaks()
+            Ok(_) => self.save(),
+            Err(e) => self.fail("Failed to delete streak", e),
+        }
+    }
+
+    fn switch_profile(&mut self, profile: String) {
+        let path = Profiles::new().path(&profile);
+        match self.db.reopen(&path.to_string_lossy()) {
+            Ok(db) => {
+                self.db = db;
+                self.profile = Some(profile);
+                self.load_streaks();
+                self.load_history();
             }
-            Err(e) => eprintln!("Failed to delete streak: {}", e),
+            Err(e) => self.fail("Failed to switch profile", e),
+        }
+    }
+
+    fn undo(&mut self) {
+        if let Err(e) = self.db.undo() {
+            self.fail("Failed to undo", e);
+        }
+        self.load_streaks();
+        self.load_history();
+    }
+
+    fn redo(&mut self) {
+        if let Err(e) = self.db.redo() {
+            self.fail("Failed to redo", e);
         }
+        self.load_streaks();
+        self.load_history();
     }
 
     fn checkin(&mut self, id: &Uuid) {
         match self.db.checkin(*id) {
-            Ok(_) => {
-                let _ = self.db.save();
-                self.load_streaks()
-            }
-            Err(e) => eprintln!("Failed to checkin: {}", e),
+            Ok(_) => self.save(),
+            Err(e) => self.fail("Failed to check in", e),
         }
     }
 
@@ -439,11 +597,8 @@ impl Streaks {
             ..Default::default()
         };
         match self.db.add(streak) {
-            Ok(_) => {
-                let _ = self.db.save();
-                self.load_streaks();
-            }
-            Err(e) => eprintln!("Failed to add streak: {}", e),
+            Ok(_) => self.save(),
+            Err(e) => self.fail("Failed to add streak", e),
         }
     }
 
@@ -473,11 +628,12 @@ impl Streaks {
 
     fn get_by_ident(&self, id: Uuid) -> Option<Streak> {
         let mut db = self.db.clone();
-        db.get_by_id(&id.to_string()[..5])
+        db.get_by_id(&id.to_string())
     }
 
     fn search(&mut self, search: String) {
-        self.streak_list = self.db.search(&search);
+        self.search = search;
+        self.load_streaks();
     }
 
     fn filter_by(&mut self, field: FilterByStatus) {
@@ -0,0 +1,183 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{self, Cipher};
+use crate::journal::Journal;
+use crate::streak::Streak;
+
+/// How many changes are kept for undo
+const MAX_HISTORY: usize = 100;
+
+/// What caused a save, so undo and redo can be told apart from regular changes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Origin {
+    Change,
+    Undo,
+    Redo,
+}
+
+/// One saved change, as the streaks it touched looked before and after it
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+    pub before: Vec<Streak>,
+    pub after: Vec<Streak>,
+}
+
+impl Change {
+    /// The change that turns `before` into `after`, if there is one
+    pub fn between(before: &[Streak], after: &[Streak]) -> Option<Self> {
+        let touched = |streak: &Streak, others: &[Streak]| !others.contains(streak);
+        let change = Self {
+            before: before.iter().filter(|s| touched(s, after)).cloned().collect(),
+            after: after.iter().filter(|s| touched(s, before)).cloned().collect(),
+        };
+        match change.before.is_empty() && change.after.is_empty() {
+            true => None,
+            false => Some(change),
+        }
+    }
+
+    pub fn reverse(&self) -> Self {
+        Self {
+            before: self.after.clone(),
+            after: self.before.clone(),
+        }
+    }
+
+    pub fn apply(&self, streaks: &mut Vec<Streak>) {
+        streaks.retain(|s| {
+            !self.before.iter().any(|b| b.id == s.id) || self.after.iter().any(|a| a.id == s.id)
+        });
+        for streak in &self.after {
+            match streaks.iter_mut().find(|s| s.id == streak.id) {
+                Some(existing) => *existing = streak.clone(),
+                None => streaks.push(streak.clone()),
+            }
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let actions = Journal::diff(&self.before, &self.after)
+            .iter()
+            .map(|action| format!("{} {}", action, action.streak().task))
+            .collect::<Vec<String>>();
+        write!(f, "{}", actions.join(", "))
+    }
+}
+
+/// Undo and redo stacks, stored next to the database file
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+impl History {
+    fn path(db_filename: &str) -> PathBuf {
+        Path::new(db_filename).with_extension("history.ron")
+    }
+
+    pub fn load(db_filename: &str, cipher: Option<&Cipher>) -> Self {
+        std::fs::read(Self::path(db_filename))
+            .ok()
+            .and_then(|data| crypto::decode(data, cipher).ok())
+            .and_then(|contents| ron::de::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, db_filename: &str, cipher: Option<&Cipher>) -> Result<(), std::io::Error> {
+        let encoded = ron::ser::to_string(self)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        std::fs::write(Self::path(db_filename), crypto::encode(&encoded, cipher)?)
+    }
+
+    /// Remember a new change, which makes anything undone so far unrecoverable
+    pub fn push(&mut self, change: Change) {
+        self.undo.push(change);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Take the most recent change off the undo stack
+    pub fn undo(&mut self) -> Option<Change> {
+        let change = self.undo.pop()?;
+        self.redo.push(change.clone());
+        Some(change)
+    }
+
+    /// Take the most recently undone change off the redo stack
+    pub fn redo(&mut self) -> Option<Change> {
+        let change = self.redo.pop()?;
+        self.undo.push(change.clone());
+        Some(change)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn change_between() {
+        let kept = Streak::new_daily("brush teeth".to_string());
+        let removed = Streak::new_daily("floss".to_string());
+        let added = Streak::new_daily("stretch".to_string());
+
+        let before = vec![kept.clone(), removed.clone()];
+        let after = vec![kept.clone(), added.clone()];
+        let change = Change::between(&before, &after).unwrap();
+        assert_eq!(change.before, vec![removed]);
+        assert_eq!(change.after, vec![added]);
+
+        assert!(Change::between(&before, &before).is_none());
+    }
+
+    #[test]
+    fn apply_and_reverse() {
+        let mut streak = Streak::new_daily("brush teeth".to_string());
+        let added = Streak::new_daily("floss".to_string());
+        let before = vec![streak.clone()];
+        streak.checkin();
+        let after = vec![streak.clone(), added];
+
+        let change = Change::between(&before, &after).unwrap();
+        let mut streaks = before.clone();
+        change.apply(&mut streaks);
+        assert_eq!(streaks, after);
+
+        change.reverse().apply(&mut streaks);
+        assert_eq!(streaks, before);
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut history = History::default();
+        let first = Change::between(&[], &[Streak::new_daily("one".to_string())]).unwrap();
+        let second = Change::between(&[], &[Streak::new_daily("two".to_string())]).unwrap();
+        history.push(first.clone());
+        history.push(second.clone());
+
+        assert_eq!(history.undo(), Some(second.clone()));
+        assert_eq!(history.undo(), Some(first.clone()));
+        assert_eq!(history.undo(), None);
+        assert_eq!(history.redo(), Some(first.clone()));
+
+        history.push(second);
+        assert!(!history.can_redo());
+        assert!(history.can_undo());
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::db::Database;
+use crate::export::Export;
+use crate::streak::{Frequency, Streak};
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ImportSource {
+    /// Loop Habit Tracker's CSV export, unzipped
+    Loop,
+    /// A CSV file with `task`, `frequency` and `date` columns, one row per check-in
+    Csv,
+    /// A skidmarks JSON export
+    Json,
+}
+
+/// A habit read from another tracker, and the days it was checked in on
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Habit {
+    pub streak: Streak,
+    pub dates: Vec<NaiveDate>,
+}
+
+impl Habit {
+    fn new(streak: Streak, mut dates: Vec<NaiveDate>) -> Self {
+        dates.sort();
+        dates.dedup();
+        Self { streak, dates }
+    }
+}
+
+/// What an import created, and which habits it skipped because a streak
+/// with the same name already exists
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    pub created: Vec<Streak>,
+    pub skipped: Vec<String>,
+    /// Days each created streak was checked in on, by id
+    pub dates: HashMap<Uuid, Vec<NaiveDate>>,
+}
+
+fn invalid(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+/// Build a streak from the dates it was checked in on
+pub fn streak_from_checkins(task: String, frequency: Frequency, dates: &[NaiveDate]) -> Streak {
+    let mut streak = Streak {
+        task,
+        frequency,
+        ..Default::default()
+    };
+    streak.count_checkins(dates);
+    streak
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    value
+        .get(..10)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+}
+
+fn parse_frequency(value: &str) -> Frequency {
+    match value.trim().to_lowercase().as_str() {
+        "weekly" | "week" | "w" => Frequency::Weekly,
+        _ => Frequency::Daily,
+    }
+}
+
+fn open_csv(path: &Path) -> Result<csv::Reader<std::fs::File>, std::io::Error> {
+    csv::Reader::from_path(path).map_err(|err| invalid(format!("{}: {err}", path.display())))
+}
+
+/// Position of the first of `names` in a CSV header
+fn column(headers: &csv::StringRecord, names: &[&str]) -> Option<usize> {
+    headers.iter().position(|header| {
+        names
+            .iter()
+            .any(|name| header.trim().eq_ignore_ascii_case(name))
+    })
+}
+
+fn read_csv(path: &Path) -> Result<Vec<Habit>, std::io::Error> {
+    let mut reader = open_csv(path)?;
+    let headers = reader
+        .headers()
+        .map_err(|err| invalid(err.to_string()))?
+        .clone();
+    let task = column(&headers, &["task", "name", "habit"])
+        .ok_or_else(|| invalid("The CSV file needs a task or name column".to_string()))?;
+    let frequency = column(&headers, &["frequency"]);
+    let date = column(&headers, &["date", "checked_in_at"]);
+
+    // Rows are grouped by name, ignoring case, keeping the first spelling seen
+    let mut habits: BTreeMap<String, (String, Option<Frequency>, Vec<NaiveDate>)> = BTreeMap::new();
+    for record in reader.records() {
+        let record = record.map_err(|err| invalid(err.to_string()))?;
+        let name = record.get(task).unwrap_or_default().trim().to_string();
+        if name.is_empty() {
+            continue;
+        }
+        let habit = habits
+            .entry(name.to_lowercase())
+            .or_insert_with(|| (name, None, vec![]));
+        if let Some(value) = frequency
+            .and_then(|i| record.get(i))
+            .filter(|v| !v.is_empty())
+        {
+            habit.1.get_or_insert(parse_frequency(value));
+        }
+        if let Some(date) = date.and_then(|i| record.get(i)).and_then(parse_date) {
+            habit.2.push(date);
+        }
+    }
+    Ok(habits
+        .into_values()
+        .map(|(name, frequency, dates)| {
+            let streak = streak_from_checkins(name, frequency.unwrap_or_default(), &dates);
+            Habit::new(streak, dates)
+        })
+        .collect())
+}
+
+/// Read Loop Habit Tracker's `Habits.csv`, and `Checkmarks.csv` next to it if there is one
+fn read_loop(path: &Path) -> Result<Vec<Habit>, std::io::Error> {
+    let habits_path = match path.is_dir() {
+        true => path.join("Habits.csv"),
+        false => path.to_path_buf(),
+    };
+    let mut reader = open_csv(&habits_path)?;
+    let headers = reader
+        .headers()
+        .map_err(|err| invalid(err.to_string()))?
+        .clone();
+    let name = column(&headers, &["Name"])
+        .ok_or_else(|| invalid("Habits.csv needs a Name column".to_string()))?;
+    let interval = column(&headers, &["Interval", "FrequencyDenominator"]);
+
+    let mut habits: Vec<(String, Frequency)> = vec![];
+    for record in reader.records() {
+        let record = record.map_err(|err| invalid(err.to_string()))?;
+        let days = interval
+            .and_then(|i| record.get(i))
+            .and_then(|value| value.trim().parse::<u32>().ok())
+            .unwrap_or(1);
+        let frequency = match days >= 7 {
+            true => Frequency::Weekly,
+            false => Frequency::Daily,
+        };
+        habits.push((record.get(name).unwrap_or_default().to_string(), frequency));
+    }
+
+    let checkmarks_path: PathBuf = habits_path.with_file_name("Checkmarks.csv");
+    let mut dates: BTreeMap<String, Vec<NaiveDate>> = BTreeMap::new();
+    if checkmarks_path.exists() {
+        let mut reader = open_csv(&checkmarks_path)?;
+        let headers = reader
+            .headers()
+            .map_err(|err| invalid(err.to_string()))?
+            .clone();
+        for record in reader.records() {
+            let record = record.map_err(|err| invalid(err.to_string()))?;
+            let Some(date) = record.get(0).and_then(parse_date) else {
+                continue;
+            };
+            for (habit, value) in headers.iter().zip(record.iter()).skip(1) {
+                // Loop marks manual check-ins with 2, and days it filled in itself with 1
+                if matches!(value.trim(), "2" | "YES_MANUAL") {
+                    dates.entry(habit.to_string()).or_default().push(date);
+                }
+            }
+        }
+    }
+
+    Ok(habits
+        .into_iter()
+        .map(|(name, frequency)| {
+            let checkins = dates.get(&name).cloned().unwrap_or_default();
+            Habit::new(streak_from_checkins(name, frequency, &checkins), checkins)
+        })
+        .collect())
+}
+
+fn read_json(path: &Path) -> Result<Vec<Habit>, std::io::Error> {
+    let contents = std::fs::read_to_string(path)?;
+    let export: Export = serde_json::from_str(&contents).map_err(|err| invalid(err.to_string()))?;
+    Ok(export
+        .streaks
+        .into_iter()
+        .map(|record| {
+            let dates = export
+                .checkins
+                .iter()
+                .filter(|checkin| checkin.streak_id == record.id)
+                .map(|checkin| {
+                    checkin
+                        .date
+                        .unwrap_or_else(|| Config::current().day_of(checkin.checked_in_at))
+                })
+                .collect();
+            let streak = Streak {
+                task: record.task,
+                frequency: parse_frequency(&record.frequency),
+                last_checkin: record.last_checkin,
+                current_streak: record.current_streak,
+                longest_streak: record.longest_streak,
+                total_checkins: record.total_checkins,
+                tags: record.tags,
+                archived: record.archived,
+                ..Default::default()
+            };
+            Habit::new(streak, dates)
+        })
+        .collect())
+}
+
+/// Read the habits in a file exported from another tracker. Runs that were
+/// already broken by `today` don't count as current.
+pub fn read(
+    source: ImportSource,
+    path: &Path,
+    today: NaiveDate,
+) -> Result<Vec<Habit>, std::io::Error> {
+    if !path.exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{} not found", path.display()),
+        ));
+    }
+    let mut habits = match source {
+        ImportSource::Loop => read_loop(path),
+        ImportSource::Csv => read_csv(path),
+        ImportSource::Json => read_json(path),
+    }?;
+    for habit in &mut habits {
+        if habit.streak.missed_as_of(today) {
+            habit.streak.current_streak = 0;
+        }
+    }
+    Ok(habits)
+}
+
+/// Work out which streaks an import would create, leaving out any whose name
+/// is already taken. Nothing is added to the database.
+pub fn plan(db: &Database, habits: Vec<Habit>) -> ImportSummary {
+    let key = |task: &str| task.trim().to_lowercase();
+    let mut taken: Vec<String> = db.streaks.iter().map(|s| key(&s.task)).collect();
+    let mut summary = ImportSummary::default();
+    for Habit { streak, dates } in habits {
+        match taken.contains(&key(&streak.task)) {
+            true => summary.skipped.push(streak.task),
+            false => {
+                taken.push(key(&streak.task));
+                summary.dates.insert(streak.id, dates);
+                summary.created.push(streak);
+            }
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+    use chrono::{Local, TimeZone};
+
+    use super::*;
+
+    #[test]
+    fn checkins_make_streaks() {
+        let date = |d| NaiveDate::from_ymd_opt(2024, 8, d).unwrap();
+        let dates = [date(1), date(2), date(3), date(5), date(6), date(6)];
+        let streak = streak_from_checkins("Walk".to_string(), Frequency::Daily, &dates);
+        assert_eq!(streak.total_checkins, 5);
+        assert_eq!(streak.longest_streak, 3);
+        assert_eq!(streak.current_streak, 2);
+        assert_eq!(streak.last_checkin, Some(date(6)));
+
+        let streak = streak_from_checkins("Walk".to_string(), Frequency::Weekly, &dates);
+        assert_eq!(streak.current_streak, 5);
+    }
+
+    #[test]
+    fn read_loop_export() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.child("Habits.csv")
+            .write_str("Position,Name,Question,Description,NumRepetitions,Interval,Color\n001,Meditate,,,1,1,#FF8F00\n002,Long run,,,1,7,#FF8F00\n")
+            .unwrap();
+        temp.child("Checkmarks.csv")
+            .write_str(
+                "Date,Meditate,Long run,\n2024-08-03,2,1,\n2024-08-02,2,2,\n2024-08-01,0,1,\n",
+            )
+            .unwrap();
+
+        let today = NaiveDate::from_ymd_opt(2024, 8, 4).unwrap();
+        let habits = read(ImportSource::Loop, temp.path(), today).unwrap();
+        let streaks: Vec<&Streak> = habits.iter().map(|habit| &habit.streak).collect();
+        assert_eq!(streaks.len(), 2);
+        assert_eq!(streaks[0].task, "Meditate");
+        assert_eq!(streaks[0].total_checkins, 2);
+        assert_eq!(streaks[0].current_streak, 2);
+        assert_eq!(streaks[1].frequency, Frequency::Weekly);
+        assert_eq!(streaks[1].total_checkins, 1);
+        assert_eq!(
+            habits[0].dates,
+            vec![
+                NaiveDate::from_ymd_opt(2024, 8, 2).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 8, 3).unwrap()
+            ]
+        );
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn read_json_export() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let json = temp.child("export.json");
+        let noon = |d| {
+            Local
+                .with_ymd_and_hms(2024, 8, d, 12, 0, 0)
+                .unwrap()
+                .to_rfc3339()
+        };
+        json.write_str(&format!(
+            r#"{{"version":1,"streaks":[{{"id":"00e8a16c-0edd-4e90-8c3f-2ee7aa6a2210","task":"Poop","frequency":"daily","status":"missed","last_checkin":"2024-08-02","current_streak":2,"longest_streak":2,"total_checkins":2}}],"checkins":[{{"streak_id":"00e8a16c-0edd-4e90-8c3f-2ee7aa6a2210","task":"Poop","date":"2024-08-01","checked_in_at":"{}"}},{{"streak_id":"00e8a16c-0edd-4e90-8c3f-2ee7aa6a2210","task":"Poop","checked_in_at":"{}"}}]}}"#,
+            noon(3),
+            noon(2)
+        ))
+        .unwrap();
+
+        let habits = read(ImportSource::Json, json.path(), Config::current().today()).unwrap();
+        assert_eq!(habits.len(), 1);
+        let date = |d| NaiveDate::from_ymd_opt(2024, 8, d).unwrap();
+        // The day recorded wins over the time; older exports only have the time
+        assert_eq!(habits[0].dates, vec![date(1), date(2)]);
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn plan_skips_existing_names() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let csv = temp.child("habits.csv");
+        csv.write_str(
+            "task,frequency,date\nPoop,daily,2024-08-01\npoop ,daily,2024-08-02\nRead,weekly,\n",
+        )
+        .unwrap();
+        let db_file = temp.child("test_plan_skips_existing_names.ron");
+        let mut db = Database::new(db_file.to_str().unwrap()).unwrap();
+        db.add(Streak::new_daily("Read".to_string())).unwrap();
+
+        let habits = read(ImportSource::Csv, csv.path(), Config::current().today()).unwrap();
+        let summary = plan(&db, habits);
+        assert_eq!(summary.created.len(), 1);
+        let poop = &summary.created[0];
+        assert_eq!(poop.task, "Poop");
+        assert_eq!(poop.total_checkins, 2);
+        assert_eq!(poop.longest_streak, 2);
+        // The run ended long ago, so it isn't current any more
+        assert_eq!(poop.current_streak, 0);
+        assert_eq!(summary.dates[&poop.id].len(), 2);
+        assert_eq!(summary.skipped, vec!["Read"]);
+
+        temp.close().unwrap();
+    }
+}
@@ -0,0 +1,379 @@
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::hash::{DefaultHasher, Hasher};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::history::Origin;
+use crate::streak::Streak;
+
+/// Write a fresh snapshot once this many events have been recorded since the last one
+const SNAPSHOT_INTERVAL: usize = 100;
+
+/// A single change to the list of streaks
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+    Add(Streak),
+    CheckIn(Streak),
+    Edit(Streak),
+    Delete(Streak),
+    /// Check-ins from before the streak was kept here, like ones imported
+    /// from another tracker. The streak itself doesn't change.
+    Backfill(Streak, Vec<NaiveDate>),
+    Undo(Box<Action>),
+    Redo(Box<Action>),
+}
+
+impl Action {
+    /// The streak as it was after the change (or before it, for deletes)
+    pub fn streak(&self) -> &Streak {
+        match self {
+            Action::Add(streak)
+            | Action::CheckIn(streak)
+            | Action::Edit(streak)
+            | Action::Delete(streak)
+            | Action::Backfill(streak, _) => streak,
+            Action::Undo(action) | Action::Redo(action) => action.streak(),
+        }
+    }
+
+    fn apply(&self, streaks: &mut Vec<Streak>) {
+        let streak = self.streak();
+        match self {
+            Action::Undo(action) | Action::Redo(action) => action.apply(streaks),
+            Action::Delete(_) => streaks.retain(|s| s.id != streak.id),
+            _ => match streaks.iter_mut().find(|s| s.id == streak.id) {
+                Some(existing) => *existing = streak.clone(),
+                None => streaks.push(streak.clone()),
+            },
+        }
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Add(_) => write!(f, "added"),
+            Action::CheckIn(_) => write!(f, "checked in"),
+            Action::Edit(_) => write!(f, "edited"),
+            Action::Delete(_) => write!(f, "deleted"),
+            Action::Backfill(_, dates) => write!(f, "backfilled {} check-ins", dates.len()),
+            Action::Undo(action) => write!(f, "undo ({action})"),
+            Action::Redo(action) => write!(f, "redo ({action})"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub timestamp: DateTime<Local>,
+    pub action: Action,
+}
+
+/// The state of the streaks after the first `events` events in the journal
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Snapshot {
+    events: usize,
+    streaks: Vec<Streak>,
+}
+
+/// Append-only log of every change made to a database.
+///
+/// When a journal exists next to the database file it is the source of truth;
+/// the database file is still written so it always holds the latest state.
+/// Edits made to it outside skidmarks are noticed by comparing it with what
+/// skidmarks last wrote, and recorded as events of their own.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Journal {
+    path: PathBuf,
+    snapshot_path: PathBuf,
+    /// Fingerprint of the database file as skidmarks last wrote it
+    written_path: PathBuf,
+}
+
+impl Journal {
+    fn for_database(db_filename: &str) -> Self {
+        let path = Path::new(db_filename);
+        Self {
+            path: path.with_extension("journal.ron"),
+            snapshot_path: path.with_extension("snapshot.ron"),
+            written_path: path.with_extension("written"),
+        }
+    }
+
+    /// Open the journal for a database, if journal mode is turned on
+    pub fn open(db_filename: &str) -> Option<Self> {
+        let journal = Self::for_database(db_filename);
+        journal.path.exists().then_some(journal)
+    }
+
+    /// Turn on journal mode, starting from the given streaks
+    pub fn create(db_filename: &str, streaks: &[Streak]) -> Result<Self, std::io::Error> {
+        let journal = Self::for_database(db_filename);
+        File::create(&journal.path)?;
+        journal.write_snapshot(0, streaks)?;
+        journal.wrote(&std::fs::read(db_filename).unwrap_or_default())?;
+        Ok(journal)
+    }
+
+    /// Turn off journal mode, discarding the history
+    pub fn remove(self) -> Result<(), std::io::Error> {
+        std::fs::remove_file(&self.path)?;
+        for path in [&self.snapshot_path, &self.written_path] {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn lines(&self) -> Result<Vec<String>, std::io::Error> {
+        let file = File::open(&self.path)?;
+        BufReader::new(file)
+            .lines()
+            .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .collect()
+    }
+
+    fn parse(line: &str) -> Result<Event, std::io::Error> {
+        ron::de::from_str(line)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+
+    /// Every event recorded so far, oldest first
+    pub fn events(&self) -> Result<Vec<Event>, std::io::Error> {
+        self.lines()?.iter().map(|line| Self::parse(line)).collect()
+    }
+
+    fn read_snapshot(&self) -> Snapshot {
+        std::fs::read_to_string(&self.snapshot_path)
+            .ok()
+            .and_then(|contents| ron::de::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn write_snapshot(&self, events: usize, streaks: &[Streak]) -> Result<(), std::io::Error> {
+        let snapshot = Snapshot {
+            events,
+            streaks: streaks.to_vec(),
+        };
+        let encoded = ron::ser::to_string(&snapshot)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        std::fs::write(&self.snapshot_path, encoded)
+    }
+
+    /// Rebuild the streaks from the latest snapshot and the events after it
+    pub fn replay(&self) -> Result<Vec<Streak>, std::io::Error> {
+        let lines = self.lines()?;
+        let mut snapshot = self.read_snapshot();
+        if snapshot.events > lines.len() {
+            snapshot = Snapshot::default();
+        }
+
+        let mut streaks = snapshot.streaks;
+        for line in &lines[snapshot.events..] {
+            Self::parse(line)?.action.apply(&mut streaks);
+        }
+        Ok(streaks)
+    }
+
+    /// Work out which actions turn `before` into `after`
+    pub fn diff(before: &[Streak], after: &[Streak]) -> Vec<Action> {
+        let mut actions: Vec<Action> = before
+            .iter()
+            .filter(|b| !after.iter().any(|a| a.id == b.id))
+            .map(|b| Action::Delete(b.clone()))
+            .collect();
+
+        for streak in after {
+            match before.iter().find(|b| b.id == streak.id) {
+                None => actions.push(Action::Add(streak.clone())),
+                Some(old) if old == streak => {}
+                Some(old) if old.total_checkins < streak.total_checkins => {
+                    actions.push(Action::CheckIn(streak.clone()))
+                }
+                Some(_) => actions.push(Action::Edit(streak.clone())),
+            }
+        }
+        actions
+    }
+
+    fn append(&self, actions: impl IntoIterator<Item = Action>) -> Result<(), std::io::Error> {
+        let timestamp = Local::now();
+        let mut file = OpenOptions::new().append(true).open(&self.path)?;
+        for action in actions {
+            let event = Event { timestamp, action };
+            let encoded = ron::ser::to_string(&event)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+            writeln!(file, "{encoded}")?;
+        }
+        Ok(())
+    }
+
+    /// Only ever compared with another fingerprint taken by the same build,
+    /// so the standard hasher is good enough
+    fn fingerprint(data: &[u8]) -> String {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(data);
+        format!("{:016x}", hasher.finish())
+    }
+
+    /// Remember the contents skidmarks just wrote to the database file
+    pub fn wrote(&self, data: &[u8]) -> Result<(), std::io::Error> {
+        std::fs::write(&self.written_path, Self::fingerprint(data))
+    }
+
+    /// Whether the database file holds something skidmarks didn't write
+    pub fn edited_outside(&self, data: &[u8]) -> bool {
+        std::fs::read_to_string(&self.written_path).ok() != Some(Self::fingerprint(data))
+    }
+
+    /// Record changes made to the database file outside skidmarks. A counter
+    /// raised by hand is an edit, not a check-in.
+    pub fn record_outside_edits(
+        &self,
+        before: &[Streak],
+        after: &[Streak],
+    ) -> Result<(), std::io::Error> {
+        let actions = Self::diff(before, after)
+            .into_iter()
+            .map(|action| match action {
+                Action::CheckIn(streak) => Action::Edit(streak),
+                other => other,
+            });
+        self.append(actions)
+    }
+
+    /// Append the days given streaks were checked in on before they were
+    /// kept here, then the changes between `before` and `after`, taking a new
+    /// snapshot when due
+    pub fn record(
+        &self,
+        before: &[Streak],
+        after: &[Streak],
+        origin: Origin,
+        backfills: &[(Streak, Vec<NaiveDate>)],
+    ) -> Result<(), std::io::Error> {
+        let actions = Self::diff(before, after);
+        if actions.is_empty() && backfills.is_empty() {
+            return Ok(());
+        }
+
+        let backfills = backfills
+            .iter()
+            .map(|(streak, dates)| Action::Backfill(streak.clone(), dates.clone()));
+        let actions = actions.into_iter().map(|action| match origin {
+            Origin::Change => action,
+            Origin::Undo => Action::Undo(Box::new(action)),
+            Origin::Redo => Action::Redo(Box::new(action)),
+        });
+        self.append(backfills.chain(actions))?;
+
+        // A snapshot ahead of the journal, say after restoring an older
+        // journal from a backup, is replaced the same as a stale one
+        let events = self.lines()?.len();
+        let snapshot = self.read_snapshot().events;
+        if snapshot > events || events - snapshot >= SNAPSHOT_INTERVAL {
+            self.write_snapshot(events, after)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn diff_classifies_changes() {
+        let kept = Streak::new_daily("brush teeth".to_string());
+        let removed = Streak::new_daily("floss".to_string());
+        let mut checked_in = Streak::new_weekly("read".to_string());
+        let mut edited = Streak::new_daily("walk".to_string());
+        let before = vec![kept.clone(), removed.clone(), checked_in.clone(), edited.clone()];
+
+        checked_in.checkin();
+        edited.task = "take a walk".to_string();
+        let added = Streak::new_daily("stretch".to_string());
+        let after = vec![kept, checked_in.clone(), edited.clone(), added.clone()];
+
+        let actions = Journal::diff(&before, &after);
+        assert_eq!(
+            actions,
+            vec![
+                Action::Delete(removed),
+                Action::CheckIn(checked_in),
+                Action::Edit(edited),
+                Action::Add(added),
+            ]
+        );
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_record_and_replay.ron");
+        let file_path = db_file.to_str().unwrap();
+
+        let mut streak = Streak::new_daily("brush teeth".to_string());
+        let journal = Journal::create(file_path, &[]).unwrap();
+        journal.record(&[], &[streak.clone()], Origin::Change, &[]).unwrap();
+
+        let before = vec![streak.clone()];
+        streak.checkin();
+        journal.record(&before, &[streak.clone()], Origin::Change, &[]).unwrap();
+
+        assert_eq!(journal.events().unwrap().len(), 2);
+        assert_eq!(journal.replay().unwrap(), vec![streak]);
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn snapshot_after_interval() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_snapshot_after_interval.ron");
+        let file_path = db_file.to_str().unwrap();
+
+        let journal = Journal::create(file_path, &[]).unwrap();
+        let mut streaks = vec![];
+        for i in 0..SNAPSHOT_INTERVAL {
+            let before = streaks.clone();
+            streaks.push(Streak::new_daily(format!("streak {i}")));
+            journal
+                .record(&before, &streaks, Origin::Change, &[])
+                .unwrap();
+        }
+
+        let snapshot = journal.read_snapshot();
+        assert_eq!(snapshot.events, SNAPSHOT_INTERVAL);
+        assert_eq!(snapshot.streaks, streaks);
+        assert_eq!(journal.replay().unwrap(), streaks);
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn record_replaces_snapshot_ahead_of_journal() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_record_replaces_snapshot_ahead_of_journal.ron");
+        let file_path = db_file.to_str().unwrap();
+
+        let streak = Streak::new_daily("brush teeth".to_string());
+        let journal = Journal::create(file_path, &[]).unwrap();
+        journal.write_snapshot(SNAPSHOT_INTERVAL, &[]).unwrap();
+        journal.record(&[], &[streak.clone()], Origin::Change, &[]).unwrap();
+
+        let snapshot = journal.read_snapshot();
+        assert_eq!(snapshot.events, 1);
+        assert_eq!(snapshot.streaks, vec![streak.clone()]);
+        assert_eq!(journal.replay().unwrap(), vec![streak]);
+
+        temp.close().unwrap();
+    }
+}
@@ -1,8 +1,18 @@
 pub mod cli;
 pub mod color;
+pub mod config;
+pub mod crypto;
 pub mod db;
+pub mod export;
 pub mod filtering;
 pub mod gui;
+pub mod history;
+pub mod import;
+pub mod journal;
+pub mod profile;
+pub mod report;
 pub mod sorting;
+pub mod stats;
 pub mod streak;
+pub mod sync;
 pub mod tui;
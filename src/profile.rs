@@ -0,0 +1,170 @@
+use std::path::PathBuf;
+
+use crate::db::Database;
+
+/// The profile that uses the original `skidmarks.ron` database
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Files kept next to a database that belong to it
+const SIDECAR_EXTENSIONS: [&str; 4] = ["history.ron", "journal.ron", "snapshot.ron", "sync.ron"];
+
+/// Named databases kept side by side in the data directory
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profiles {
+    dir: PathBuf,
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiles {
+    pub fn new() -> Self {
+        Self::in_dir(dirs::data_local_dir().unwrap_or_default())
+    }
+
+    pub fn in_dir(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Path of the database file for a profile
+    pub fn path(&self, name: &str) -> PathBuf {
+        match name {
+            DEFAULT_PROFILE => self.dir.join("skidmarks.ron"),
+            _ => self.dir.join("skidmarks").join(format!("{name}.ron")),
+        }
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        name == DEFAULT_PROFILE || self.path(name).exists()
+    }
+
+    /// All profiles, starting with the default one
+    pub fn list(&self) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(self.dir.join("skidmarks"))
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+                    .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+                    .filter(|name| Self::validate(name).is_ok() && name != DEFAULT_PROFILE)
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names.insert(0, DEFAULT_PROFILE.to_string());
+        names
+    }
+
+    fn validate(name: &str) -> Result<(), std::io::Error> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        match valid {
+            true => Ok(()),
+            false => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid profile name \"{name}\", use letters, numbers, - and _"),
+            )),
+        }
+    }
+
+    pub fn create(&self, name: &str) -> Result<PathBuf, std::io::Error> {
+        Self::validate(name)?;
+        if self.exists(name) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("Profile \"{name}\" already exists"),
+            ));
+        }
+        let path = self.path(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Database::new(&path.to_string_lossy())?;
+        Ok(path)
+    }
+
+    /// Delete a profile's database and everything stored alongside it
+    pub fn delete(&self, name: &str) -> Result<(), std::io::Error> {
+        if name == DEFAULT_PROFILE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "The default profile can't be deleted",
+            ));
+        }
+        Self::validate(name)?;
+        if !self.exists(name) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Profile \"{name}\" not found"),
+            ));
+        }
+        let path = self.path(name);
+        for extension in SIDECAR_EXTENSIONS {
+            let sidecar = path.with_extension(extension);
+            if sidecar.exists() {
+                std::fs::remove_file(sidecar)?;
+            }
+        }
+        std::fs::remove_file(path)
+    }
+
+    /// The profile after `name`, wrapping around to the first
+    pub fn next(&self, name: &str) -> String {
+        let names = self.list();
+        let i = names.iter().position(|n| n == name).map_or(0, |i| i + 1);
+        names[i % names.len()].clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_list_and_delete() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let profiles = Profiles::in_dir(temp.path().to_path_buf());
+        assert_eq!(profiles.list(), vec![DEFAULT_PROFILE]);
+
+        let path = profiles.create("work").unwrap();
+        assert!(path.exists());
+        profiles.create("home").unwrap();
+        assert_eq!(profiles.list(), vec![DEFAULT_PROFILE, "home", "work"]);
+        assert!(profiles.create("work").is_err());
+
+        profiles.delete("work").unwrap();
+        assert_eq!(profiles.list(), vec![DEFAULT_PROFILE, "home"]);
+        assert!(profiles.delete("work").is_err());
+        assert!(profiles.delete(DEFAULT_PROFILE).is_err());
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn invalid_names() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let profiles = Profiles::in_dir(temp.path().to_path_buf());
+        assert!(profiles.create("").is_err());
+        assert!(profiles.create("../work").is_err());
+        assert!(profiles.create("my work").is_err());
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn next_wraps_around() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let profiles = Profiles::in_dir(temp.path().to_path_buf());
+        profiles.create("work").unwrap();
+        assert_eq!(profiles.next(DEFAULT_PROFILE), "work");
+        assert_eq!(profiles.next("work"), DEFAULT_PROFILE);
+
+        temp.close().unwrap();
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate, TimeDelta, Weekday};
+use clap::ValueEnum;
+
+use crate::color::ReportStyles;
+use crate::config::Config;
+use crate::stats::{self, CheckinHistory};
+use crate::streak::Streak;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum ReportPeriod {
+    /// Seven days from the configured start of the week
+    #[default]
+    Week,
+    Month,
+    Year,
+}
+
+impl ReportPeriod {
+    /// First and last day of the period `today` falls in
+    pub fn range(&self, today: NaiveDate, week_start: Weekday) -> (NaiveDate, NaiveDate) {
+        let first = match self {
+            ReportPeriod::Week => {
+                today - TimeDelta::days(stats::days_into_week(today.weekday(), week_start) as i64)
+            }
+            ReportPeriod::Month => today.with_day(1).unwrap_or(today),
+            ReportPeriod::Year => today.with_ordinal(1).unwrap_or(today),
+        };
+        let last = match self {
+            ReportPeriod::Week => first + TimeDelta::days(6),
+            ReportPeriod::Month => first
+                .checked_add_months(chrono::Months::new(1))
+                .map_or(today, |next| next - TimeDelta::days(1)),
+            ReportPeriod::Year => NaiveDate::from_ymd_opt(first.year(), 12, 31).unwrap_or(today),
+        };
+        (first, last)
+    }
+
+    fn title(&self, first: NaiveDate) -> String {
+        match self {
+            ReportPeriod::Week => format!("Week of {}", first.format("%B %-d, %Y")),
+            ReportPeriod::Month => first.format("%B %Y").to_string(),
+            ReportPeriod::Year => first.format("%Y").to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum ReportFormat {
+    #[default]
+    Markdown,
+    /// A single page with no outside files
+    Html,
+}
+
+/// How one streak did over the period
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreakReport {
+    pub task: String,
+    pub checkins: usize,
+    /// `None` if the streak wasn't tracked during the period
+    pub completion: Option<f64>,
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    /// Missed a check-in after having been kept up
+    pub broken: bool,
+    /// Ran longer than it ever had before the period
+    pub new_record: bool,
+}
+
+impl StreakReport {
+    fn new(streak: &Streak, history: &CheckinHistory, first: NaiveDate, last: NaiveDate) -> Self {
+        let dates = history.dates(streak.id);
+        let in_period = |date: &NaiveDate| *date >= first && *date <= last;
+        let start = history
+            .start(streak.id)
+            .map_or(first, |start| start.max(first));
+        let completion =
+            (start <= last).then(|| stats::completion(&streak.frequency, dates, start, last));
+
+        let lengths = stats::run_lengths(&streak.frequency, dates);
+        let best_before = dates
+            .iter()
+            .zip(&lengths)
+            .filter(|(date, _)| **date < first)
+            .map(|(_, length)| *length)
+            .max()
+            .unwrap_or(0);
+        let best_during = dates
+            .iter()
+            .zip(&lengths)
+            .filter(|(date, _)| in_period(date))
+            .map(|(_, length)| *length)
+            .max()
+            .unwrap_or(0);
+        let broken = (0..=(last - first).num_days())
+            .map(|offset| first + TimeDelta::days(offset))
+            .filter(|day| *day < last)
+            .any(|day| {
+                history.day_status(streak, day, last) == stats::DayStatus::Missed
+                    && dates.iter().any(|date| *date < day)
+            });
+
+        Self {
+            task: streak.task.clone(),
+            checkins: dates.iter().filter(|date| in_period(date)).count(),
+            completion,
+            current_streak: streak.current_streak,
+            longest_streak: streak.longest_streak,
+            broken,
+            new_record: best_during > best_before && best_during > 1,
+        }
+    }
+
+    fn notes(&self) -> String {
+        let mut notes = vec![];
+        if self.new_record {
+            notes.push("🏆 new record");
+        }
+        if self.broken {
+            notes.push("💔 broken");
+        }
+        notes.join(", ")
+    }
+}
+
+/// A summary of a week, month or year of check-ins
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub title: String,
+    pub first: NaiveDate,
+    /// The last day covered, which is today for the period in progress
+    pub last: NaiveDate,
+    pub streaks: Vec<StreakReport>,
+    /// Share of streaks checked in on each day
+    pub ratios: BTreeMap<NaiveDate, f64>,
+}
+
+fn percent(rate: Option<f64>) -> String {
+    rate.map_or("-".to_string(), |rate| format!("{:.0}%", rate * 100.0))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Report {
+    pub fn new(
+        streaks: &[Streak],
+        history: &CheckinHistory,
+        period: ReportPeriod,
+        today: NaiveDate,
+    ) -> Self {
+        let (first, last) = period.range(today, Config::current().week_start());
+        let last = last.min(today);
+        let ratios = history
+            .daily_ratios(streaks)
+            .into_iter()
+            .filter(|(date, _)| *date >= first && *date <= last)
+            .collect();
+        Self {
+            title: period.title(first),
+            first,
+            last,
+            streaks: streaks
+                .iter()
+                .map(|streak| StreakReport::new(streak, history, first, last))
+                .collect(),
+            ratios,
+        }
+    }
+
+    pub fn checkins(&self) -> usize {
+        self.streaks.iter().map(|s| s.checkins).sum()
+    }
+
+    /// Average completion of the streaks that were tracked
+    pub fn completion(&self) -> Option<f64> {
+        let rates: Vec<f64> = self.streaks.iter().filter_map(|s| s.completion).collect();
+        (!rates.is_empty()).then(|| rates.iter().sum::<f64>() / rates.len() as f64)
+    }
+
+    fn days(&self) -> impl Iterator<Item = NaiveDate> + '_ {
+        (0..=(self.last - self.first).num_days()).map(|offset| self.first + TimeDelta::days(offset))
+    }
+
+    /// The days of the period grouped into months, as (label, days)
+    fn months(&self) -> Vec<(String, Vec<NaiveDate>)> {
+        let mut months: Vec<(String, Vec<NaiveDate>)> = vec![];
+        for day in self.days() {
+            let label = day.format("%b").to_string();
+            match months.last_mut() {
+                Some((last, days)) if *last == label => days.push(day),
+                _ => months.push((label, vec![day])),
+            }
+        }
+        months
+    }
+
+    fn level(&self, day: NaiveDate) -> usize {
+        stats::level(self.ratios.get(&day).copied().unwrap_or(0.0))
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Markdown => self.markdown(),
+            ReportFormat::Html => self.html(),
+        }
+    }
+
+    fn markdown(&self) -> String {
+        let cells = ["⬜", "🟨", "🟨", "🟩", "🟩"];
+        let mut lines = vec![
+            format!("# Skidmarks: {}", self.title),
+            String::new(),
+            format!(
+                "{} check-ins from {} to {}, {} done on average.",
+                self.checkins(),
+                self.first,
+                self.last,
+                percent(self.completion())
+            ),
+            String::new(),
+            "| Streak | Check-ins | Done | Current | Longest | |".to_string(),
+            "|---|---:|---:|---:|---:|---|".to_string(),
+        ];
+        for streak in &self.streaks {
+            lines.push(format!(
+                "| {} | {} | {} | {} | {} | {} |",
+                streak.task.replace('|', "\\|"),
+                streak.checkins,
+                percent(streak.completion),
+                streak.current_streak,
+                streak.longest_streak,
+                streak.notes()
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push("## Check-ins".to_string());
+        lines.push(String::new());
+        for (label, days) in self.months() {
+            let row: String = days.iter().map(|day| cells[self.level(*day)]).collect();
+            lines.push(format!("    {label} {row}"));
+        }
+        lines.join("\n")
+    }
+
+    fn html(&self) -> String {
+        let styles = ReportStyles::new();
+        let mut rows = String::new();
+        for streak in &self.streaks {
+            rows.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape_html(&streak.task),
+                streak.checkins,
+                percent(streak.completion),
+                streak.current_streak,
+                streak.longest_streak,
+                streak.notes()
+            ));
+        }
+
+        let mut heatmap = String::new();
+        for (label, days) in self.months() {
+            let cells: String = days
+                .iter()
+                .map(|day| {
+                    format!(
+                        "<span class=\"cell\" title=\"{day}\" style=\"background:{}\"></span>",
+                        styles.heatmap[self.level(*day)]
+                    )
+                })
+                .collect();
+            heatmap.push_str(&format!(
+                "<div><span class=\"month\">{label}</span>{cells}</div>\n"
+            ));
+        }
+
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Skidmarks: {title}</title>
+<style>
+body {{ background: {background}; color: {foreground}; font-family: sans-serif; margin: 2em; }}
+h1, h2 {{ color: {accent}; }}
+table {{ border-collapse: collapse; }}
+th, td {{ padding: 0.3em 0.8em; border-bottom: 1px solid {surface}; text-align: left; }}
+.month {{ display: inline-block; width: 3em; }}
+.cell {{ display: inline-block; width: 0.8em; height: 0.8em; margin: 1px; border-radius: 2px; }}
+</style>
+</head>
+<body>
+<h1>Skidmarks: {title}</h1>
+<p>{checkins} check-ins from {first} to {last}, {completion} done on average.</p>
+<table>
+<tr><th>Streak</th><th>Check-ins</th><th>Done</th><th>Current</th><th>Longest</th><th></th></tr>
+{rows}</table>
+<h2>Check-ins</h2>
+{heatmap}</body>
+</html>
+"#,
+            title = escape_html(&self.title),
+            background = styles.background,
+            foreground = styles.foreground,
+            accent = styles.accent,
+            surface = styles.surface,
+            checkins = self.checkins(),
+            first = self.first,
+            last = self.last,
+            completion = percent(self.completion()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+    use chrono::Local;
+
+    use super::*;
+    use crate::db::Database;
+
+    #[test]
+    fn period_ranges() {
+        let day = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        let monday = Weekday::Mon;
+        assert_eq!(
+            ReportPeriod::Week.range(day(8, 7), monday),
+            (day(8, 5), day(8, 11))
+        );
+        assert_eq!(
+            ReportPeriod::Week.range(day(8, 7), Weekday::Sun),
+            (day(8, 4), day(8, 10))
+        );
+        assert_eq!(
+            ReportPeriod::Month.range(day(2, 7), monday),
+            (day(2, 1), day(2, 29))
+        );
+        assert_eq!(
+            ReportPeriod::Year.range(day(8, 7), monday),
+            (day(1, 1), day(12, 31))
+        );
+        assert_eq!(
+            ReportPeriod::Week.title(day(8, 5)),
+            "Week of August 5, 2024"
+        );
+    }
+
+    #[test]
+    fn report_formats() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_report_formats.ron");
+        let mut db = Database::new(db_file.to_str().unwrap()).unwrap();
+        db.enable_journal().unwrap();
+        db.add(Streak::new_daily("Poop <daily>".to_string()))
+            .unwrap();
+        db.add(Streak::new_daily("Floss".to_string())).unwrap();
+        db.save().unwrap();
+        db.checkin(db.streaks[0].id).unwrap();
+        db.save().unwrap();
+
+        let history = CheckinHistory::load(&db).unwrap().unwrap();
+        let today = Local::now().date_naive();
+        let report = Report::new(&db.streaks, &history, ReportPeriod::Month, today);
+        assert_eq!(report.checkins(), 1);
+        assert_eq!(report.completion(), Some(0.5));
+        assert_eq!(report.last, today);
+
+        let markdown = report.render(ReportFormat::Markdown);
+        assert!(markdown.contains("| Poop <daily> | 1 | 100% | 1 | 1 |  |"));
+        assert!(markdown.ends_with('🟨'));
+
+        let html = report.render(ReportFormat::Html);
+        assert!(html.contains("<td>Poop &lt;daily&gt;</td>"));
+        assert!(html.contains("1 check-ins from"));
+
+        temp.close().unwrap();
+    }
+}
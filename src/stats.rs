@@ -0,0 +1,637 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, Weekday};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::color::{emoji, emoji_enabled};
+use crate::config::Config;
+use crate::db::Database;
+use crate::journal::{Action, Event};
+use crate::streak::{Frequency, Streak};
+
+/// Days looked back over for completion rates
+pub const WINDOWS: [i64; 4] = [7, 30, 90, 365];
+
+/// A check-in recorded by the journal
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkin {
+    pub streak_id: Uuid,
+    pub task: String,
+    /// The day it counts for
+    pub date: NaiveDate,
+    /// When it was recorded, or the start of its day when backfilled
+    pub at: DateTime<Local>,
+}
+
+/// Every check-in in the journal that wasn't undone since, oldest first.
+/// Check-ins are spotted by the count going up, and undone by it going down.
+pub fn checkins(events: &[Event]) -> Vec<Checkin> {
+    let mut totals: HashMap<Uuid, u32> = HashMap::new();
+    let mut kept: HashMap<Uuid, Vec<Checkin>> = HashMap::new();
+    for event in events {
+        let streak = event.action.streak();
+        if let Action::Backfill(_, dates) = &event.action {
+            let backfilled = dates.iter().map(|date| Checkin {
+                streak_id: streak.id,
+                task: streak.task.clone(),
+                date: *date,
+                at: date
+                    .and_time(NaiveTime::MIN)
+                    .and_local_timezone(Local)
+                    .earliest()
+                    .unwrap_or(event.timestamp),
+            });
+            kept.entry(streak.id).or_default().extend(backfilled);
+            // The count goes up by these in the change recorded after them,
+            // for streaks that were already being counted
+            if let Some(total) = totals.get_mut(&streak.id) {
+                *total += dates.len() as u32;
+            }
+            continue;
+        }
+        let previous = match (totals.get(&streak.id), &event.action) {
+            (Some(total), _) => *total,
+            (None, Action::CheckIn(_)) => streak.total_checkins.saturating_sub(1),
+            (None, _) => streak.total_checkins,
+        };
+        let checkins = kept.entry(streak.id).or_default();
+        if streak.total_checkins > previous {
+            checkins.push(Checkin {
+                streak_id: streak.id,
+                task: streak.task.clone(),
+                date: streak
+                    .last_checkin
+                    .unwrap_or_else(|| Config::current().day_of(event.timestamp)),
+                at: event.timestamp,
+            });
+        } else if streak.total_checkins < previous {
+            checkins.pop();
+        }
+        totals.insert(streak.id, streak.total_checkins);
+    }
+    let mut checkins: Vec<Checkin> = kept.into_values().flatten().collect();
+    checkins.sort_by_key(|checkin| checkin.at);
+    checkins
+}
+
+/// Check-in dates per streak, as recorded by the journal
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CheckinHistory {
+    dates: HashMap<Uuid, Vec<NaiveDate>>,
+    /// The day each streak added since the journal was turned on was added
+    added: HashMap<Uuid, NaiveDate>,
+    /// The first day anything was recorded
+    since: Option<NaiveDate>,
+}
+
+impl CheckinHistory {
+    /// Read the history from the journal, or `None` if the journal is off
+    pub fn load(db: &Database) -> Result<Option<Self>, std::io::Error> {
+        let Some(journal) = db.journal() else {
+            return Ok(None);
+        };
+        let events = journal.events()?;
+        let mut history = Self::default();
+        for event in &events {
+            let day = Config::current().day_of(event.timestamp);
+            history.since.get_or_insert(day);
+            match &event.action {
+                Action::Add(streak) => {
+                    history.added.entry(streak.id).or_insert(day);
+                }
+                // Backfilled streaks are known from their first check-in
+                Action::Backfill(streak, dates) => {
+                    let first = dates.iter().min().copied().unwrap_or(day);
+                    let added = history.added.entry(streak.id).or_insert(first);
+                    *added = (*added).min(first);
+                }
+                _ => {}
+            }
+        }
+        for checkin in checkins(&events) {
+            history
+                .dates
+                .entry(checkin.streak_id)
+                .or_default()
+                .push(checkin.date);
+        }
+        for dates in history.dates.values_mut() {
+            dates.sort();
+            dates.dedup();
+        }
+        Ok(Some(history))
+    }
+
+    pub fn dates(&self, id: Uuid) -> &[NaiveDate] {
+        self.dates.get(&id).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// The first day a streak's check-ins are known from: the day it was
+    /// added, or the first day in the journal for older streaks
+    pub fn start(&self, id: Uuid) -> Option<NaiveDate> {
+        self.added.get(&id).copied().or(self.since)
+    }
+
+    /// Share of the streaks known on each day that were checked in that day,
+    /// for days with any check-ins
+    pub fn daily_ratios(&self, streaks: &[Streak]) -> BTreeMap<NaiveDate, f64> {
+        let mut checked_in: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+        for streak in streaks {
+            for date in self.dates(streak.id) {
+                *checked_in.entry(*date).or_default() += 1;
+            }
+        }
+        checked_in
+            .into_iter()
+            .map(|(date, count)| {
+                let known = streaks
+                    .iter()
+                    .filter(|s| self.start(s.id).is_none_or(|start| start <= date))
+                    .count()
+                    .max(count);
+                (date, count as f64 / known as f64)
+            })
+            .collect()
+    }
+}
+
+/// How a streak fared on one day
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DayStatus {
+    Done,
+    Missed,
+    /// No check-in, but a weekly streak didn't need one
+    Skipped,
+    /// Today, still to be checked in
+    Waiting,
+    Future,
+    /// Before the journal knew about the streak
+    Untracked,
+}
+
+impl DayStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            DayStatus::Done => "done",
+            DayStatus::Missed => "missed",
+            DayStatus::Skipped => "skipped",
+            DayStatus::Waiting => "waiting",
+            DayStatus::Future => "future",
+            DayStatus::Untracked => "untracked",
+        }
+    }
+
+    pub fn emoji(&self) -> &'static str {
+        match self {
+            DayStatus::Done => emoji("✅", "x"),
+            DayStatus::Missed => emoji("❌", "!"),
+            DayStatus::Waiting => emoji("⏳", "?"),
+            DayStatus::Skipped => emoji("➖", "-"),
+            DayStatus::Future => emoji("·", "."),
+            DayStatus::Untracked => " ",
+        }
+    }
+
+    /// The emoji, padded to the two columns the others take up
+    pub fn marker(&self) -> String {
+        match self {
+            DayStatus::Future | DayStatus::Untracked => format!(" {}", self.emoji()),
+            _ if !emoji_enabled() => format!(" {}", self.emoji()),
+            other => other.emoji().to_string(),
+        }
+    }
+}
+
+impl CheckinHistory {
+    /// How a streak fared on `day`, judged as of `today`
+    pub fn day_status(&self, streak: &Streak, day: NaiveDate, today: NaiveDate) -> DayStatus {
+        let dates = self.dates(streak.id);
+        if day > today {
+            return DayStatus::Future;
+        }
+        if dates.contains(&day) {
+            return DayStatus::Done;
+        }
+        if self.start(streak.id).is_none_or(|start| day < start) {
+            return DayStatus::Untracked;
+        }
+        let previous = dates.iter().rev().find(|date| **date < day);
+        let covered = streak.frequency == Frequency::Weekly
+            && previous.is_some_and(|previous| (day - *previous).num_days() <= 7);
+        match (covered, day == today) {
+            (true, _) => DayStatus::Skipped,
+            (false, true) => DayStatus::Waiting,
+            (false, false) => DayStatus::Missed,
+        }
+    }
+}
+
+/// Which of the five heatmap colors a completion ratio gets
+pub fn level(ratio: f64) -> usize {
+    match ratio {
+        r if r <= 0.0 => 0,
+        r if r <= 0.25 => 1,
+        r if r <= 0.5 => 2,
+        r if r <= 0.75 => 3,
+        _ => 4,
+    }
+}
+
+/// How many days into a week starting on `week_start` a day falls
+pub fn days_into_week(day: Weekday, week_start: Weekday) -> usize {
+    (7 + day.num_days_from_monday() as usize - week_start.num_days_from_monday() as usize) % 7
+}
+
+/// Short day names for a week starting on `week_start`, like "Mon Tue Wed"
+pub fn week_header(week_start: Weekday) -> String {
+    (0..7)
+        .scan(week_start.pred(), |day, _| {
+            *day = day.succ();
+            Some(format!("{day:?}"))
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// The weeks of the month `first` falls in, starting on `week_start`, with
+/// `None` for days outside the month
+pub fn month_weeks(first: NaiveDate, week_start: Weekday) -> Vec<[Option<NaiveDate>; 7]> {
+    let first = first.with_day(1).unwrap_or(first);
+    let mut weeks = vec![];
+    let mut week = [None; 7];
+    let mut day = first;
+    while day.month() == first.month() {
+        let weekday = days_into_week(day.weekday(), week_start);
+        week[weekday] = Some(day);
+        if weekday == 6 {
+            weeks.push(week);
+            week = [None; 7];
+        }
+        day += chrono::TimeDelta::days(1);
+    }
+    if week.iter().any(Option::is_some) {
+        weeks.push(week);
+    }
+    weeks
+}
+
+/// Share of days (or weeks, for weekly streaks) with a check-in
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Completion {
+    pub days: i64,
+    pub rate: f64,
+}
+
+/// Everything known about a single streak. Fields that need the check-in
+/// history are `None` when the journal is off, except the average streak,
+/// which is then estimated from the counters.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StreakStats {
+    pub id: Uuid,
+    pub task: String,
+    pub total_checkins: u32,
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    pub completion: Option<Vec<Completion>>,
+    pub average_streak: Option<f64>,
+    pub breaks: Option<u32>,
+    /// Check-ins on each day of the week, Monday first
+    pub weekdays: Option<[u32; 7]>,
+    pub best_weekday: Option<Weekday>,
+    pub worst_weekday: Option<Weekday>,
+}
+
+/// How long the streak was as of each date in `dates`, which must be sorted
+pub fn run_lengths(frequency: &Frequency, dates: &[NaiveDate]) -> Vec<u32> {
+    let max_gap = match frequency {
+        Frequency::Daily => 1,
+        Frequency::Weekly => 7,
+    };
+    let mut lengths: Vec<u32> = vec![];
+    let mut previous: Option<NaiveDate> = None;
+    for date in dates {
+        let length = match (previous, lengths.last()) {
+            (Some(previous), Some(length)) if (*date - previous).num_days() <= max_gap => {
+                length + 1
+            }
+            _ => 1,
+        };
+        lengths.push(length);
+        previous = Some(*date);
+    }
+    lengths
+}
+
+/// Lengths of the runs of check-ins in `dates`, oldest first
+fn runs(frequency: &Frequency, dates: &[NaiveDate]) -> Vec<u32> {
+    let lengths = run_lengths(frequency, dates);
+    let mut runs: Vec<u32> = vec![];
+    for (i, length) in lengths.iter().enumerate() {
+        if lengths.get(i + 1).is_none_or(|next| *next == 1) {
+            runs.push(*length);
+        }
+    }
+    runs
+}
+
+/// Share of the days, or 7-day blocks counting back from `today`, between
+/// `start` and `today` that have a check-in
+pub fn completion(
+    frequency: &Frequency,
+    dates: &[NaiveDate],
+    start: NaiveDate,
+    today: NaiveDate,
+) -> f64 {
+    let days = (today - start).num_days() + 1;
+    if days <= 0 {
+        return 0.0;
+    }
+    let in_range = dates.iter().filter(|d| **d >= start && **d <= today);
+    let (done, periods) = match frequency {
+        Frequency::Daily => (in_range.count() as i64, days),
+        Frequency::Weekly => {
+            let mut blocks: Vec<i64> = in_range.map(|d| (today - *d).num_days() / 7).collect();
+            blocks.dedup();
+            (blocks.len() as i64, (days + 6) / 7)
+        }
+    };
+    done as f64 / periods as f64
+}
+
+/// Best and worst days of the week by number of check-ins. Ties go to the
+/// earlier day, and there is no answer without any check-ins.
+pub fn best_and_worst(weekdays: &[u32; 7]) -> Option<(Weekday, Weekday)> {
+    if weekdays.iter().all(|count| *count == 0) {
+        return None;
+    }
+    let day = |i: usize| Weekday::try_from(i as u8).unwrap_or(Weekday::Mon);
+    let best = (0..7).fold(0, |best, i| match weekdays[i] > weekdays[best] {
+        true => i,
+        false => best,
+    });
+    let worst = (0..7).fold(0, |worst, i| match weekdays[i] < weekdays[worst] {
+        true => i,
+        false => worst,
+    });
+    Some((day(best), day(worst)))
+}
+
+impl StreakStats {
+    pub fn new(streak: &Streak, history: Option<&CheckinHistory>, today: NaiveDate) -> Self {
+        let mut stats = Self {
+            id: streak.id,
+            task: streak.task.clone(),
+            total_checkins: streak.total_checkins,
+            current_streak: streak.current_streak,
+            longest_streak: streak.longest_streak,
+            completion: None,
+            average_streak: None,
+            breaks: None,
+            weekdays: None,
+            best_weekday: None,
+            worst_weekday: None,
+        };
+        let Some(history) = history else {
+            // There are at least as many runs as it takes to fit every
+            // check-in into runs no longer than the longest one
+            if streak.longest_streak > 0 {
+                let runs = streak.total_checkins.div_ceil(streak.longest_streak).max(1);
+                stats.average_streak = Some(streak.total_checkins as f64 / runs as f64);
+            }
+            return stats;
+        };
+
+        let dates = history.dates(streak.id);
+        let tracked_since = history.start(streak.id).unwrap_or(today);
+        stats.completion = Some(
+            WINDOWS
+                .iter()
+                .map(|days| {
+                    let start = (today - chrono::TimeDelta::days(days - 1)).max(tracked_since);
+                    Completion {
+                        days: *days,
+                        rate: completion(&streak.frequency, dates, start, today),
+                    }
+                })
+                .collect(),
+        );
+
+        let runs = runs(&streak.frequency, dates);
+        if !runs.is_empty() {
+            stats.average_streak = Some(runs.iter().sum::<u32>() as f64 / runs.len() as f64);
+        }
+        let broken_now = streak.is_missed() && !runs.is_empty();
+        stats.breaks = Some(runs.len().saturating_sub(1) as u32 + broken_now as u32);
+
+        let mut weekdays = [0; 7];
+        for date in dates {
+            weekdays[date.weekday().num_days_from_monday() as usize] += 1;
+        }
+        stats.weekdays = Some(weekdays);
+        if let Some((best, worst)) = best_and_worst(&weekdays) {
+            stats.best_weekday = Some(best);
+            stats.worst_weekday = Some(worst);
+        }
+        stats
+    }
+}
+
+/// Figures across every streak
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Totals {
+    pub streaks: usize,
+    pub total_checkins: u32,
+    pub longest_streak: u32,
+    /// Average of the streaks' completion rates
+    pub completion: Option<Vec<Completion>>,
+    pub breaks: Option<u32>,
+    pub weekdays: Option<[u32; 7]>,
+    pub best_weekday: Option<Weekday>,
+    pub worst_weekday: Option<Weekday>,
+}
+
+impl Totals {
+    pub fn new(stats: &[StreakStats]) -> Self {
+        let mut totals = Self {
+            streaks: stats.len(),
+            total_checkins: stats.iter().map(|s| s.total_checkins).sum(),
+            longest_streak: stats.iter().map(|s| s.longest_streak).max().unwrap_or(0),
+            ..Default::default()
+        };
+        let completions: Vec<&Vec<Completion>> =
+            stats.iter().filter_map(|s| s.completion.as_ref()).collect();
+        if completions.is_empty() {
+            return totals;
+        }
+        totals.completion = Some(
+            WINDOWS
+                .iter()
+                .enumerate()
+                .map(|(i, days)| Completion {
+                    days: *days,
+                    rate: completions.iter().map(|c| c[i].rate).sum::<f64>()
+                        / completions.len() as f64,
+                })
+                .collect(),
+        );
+        totals.breaks = Some(stats.iter().filter_map(|s| s.breaks).sum());
+        let mut weekdays = [0; 7];
+        for counts in stats.iter().filter_map(|s| s.weekdays) {
+            for (total, count) in weekdays.iter_mut().zip(counts) {
+                *total += count;
+            }
+        }
+        totals.weekdays = Some(weekdays);
+        if let Some((best, worst)) = best_and_worst(&weekdays) {
+            totals.best_weekday = Some(best);
+            totals.worst_weekday = Some(worst);
+        }
+        totals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+    use chrono::{Local, TimeDelta};
+
+    use super::*;
+
+    #[test]
+    fn stats_from_dates() {
+        let today = NaiveDate::from_ymd_opt(2024, 8, 10).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2024, 8, d).unwrap();
+        let dates = [day(1), day(2), day(3), day(6), day(9), day(10)];
+        assert_eq!(runs(&Frequency::Daily, &dates), vec![3, 1, 2]);
+        assert_eq!(runs(&Frequency::Weekly, &dates), vec![6]);
+        assert_eq!(
+            completion(&Frequency::Daily, &dates, day(4), today),
+            3.0 / 7.0
+        );
+        assert_eq!(completion(&Frequency::Weekly, &dates, day(1), today), 1.0);
+        assert_eq!(
+            best_and_worst(&[0, 3, 1, 3, 0, 0, 2]),
+            Some((Weekday::Tue, Weekday::Mon))
+        );
+        assert_eq!(best_and_worst(&[0; 7]), None);
+    }
+
+    #[test]
+    fn days_of_a_month() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 8, d).unwrap();
+        let weeks = month_weeks(day(15), Weekday::Mon);
+        assert_eq!(weeks.len(), 5);
+        assert_eq!(
+            weeks[0],
+            [
+                None,
+                None,
+                None,
+                Some(day(1)),
+                Some(day(2)),
+                Some(day(3)),
+                Some(day(4))
+            ]
+        );
+        assert_eq!(weeks[4][5], Some(day(31)));
+        let weeks = month_weeks(day(15), Weekday::Sun);
+        assert_eq!(weeks[0][4], Some(day(1)));
+        assert_eq!(weeks[4][6], Some(day(31)));
+        assert_eq!(week_header(Weekday::Sun), "Sun Mon Tue Wed Thu Fri Sat");
+
+        let streak = Streak::new_weekly("Run".to_string());
+        let history = CheckinHistory {
+            dates: HashMap::from([(streak.id, vec![day(2), day(5)])]),
+            added: HashMap::new(),
+            since: Some(day(2)),
+        };
+        let status = |d| history.day_status(&streak, day(d), day(20));
+        assert_eq!(status(1), DayStatus::Untracked);
+        assert_eq!(status(5), DayStatus::Done);
+        assert_eq!(status(12), DayStatus::Skipped);
+        assert_eq!(status(13), DayStatus::Missed);
+        assert_eq!(status(20), DayStatus::Waiting);
+        assert_eq!(status(21), DayStatus::Future);
+    }
+
+    #[test]
+    fn stats_from_journal() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_stats_from_journal.ron");
+        let mut db = Database::new(db_file.to_str().unwrap()).unwrap();
+        db.enable_journal().unwrap();
+        db.add(Streak::new_daily("Poop".to_string())).unwrap();
+        db.add(Streak::new_daily("Floss".to_string())).unwrap();
+        db.save().unwrap();
+        db.checkin(db.streaks[0].id).unwrap();
+        db.save().unwrap();
+        db.checkin(db.streaks[1].id).unwrap();
+        db.save().unwrap();
+        db.undo().unwrap();
+
+        let today = Local::now().date_naive();
+        let history = CheckinHistory::load(&db).unwrap().unwrap();
+        let stats: Vec<StreakStats> = db
+            .streaks
+            .iter()
+            .map(|s| StreakStats::new(s, Some(&history), today))
+            .collect();
+        assert_eq!(stats[0].completion.as_ref().unwrap()[0].rate, 1.0);
+        assert_eq!(stats[0].average_streak, Some(1.0));
+        assert_eq!(stats[1].completion.as_ref().unwrap()[3].rate, 0.0);
+        assert_eq!(stats[1].breaks, Some(0));
+
+        let totals = Totals::new(&stats);
+        assert_eq!(totals.streaks, 2);
+        assert_eq!(totals.total_checkins, 1);
+        assert_eq!(totals.completion.unwrap()[1].rate, 0.5);
+        assert_eq!(
+            totals.weekdays.unwrap()[today.weekday().num_days_from_monday() as usize],
+            1
+        );
+
+        db.disable_journal().unwrap();
+        assert_eq!(CheckinHistory::load(&db).unwrap(), None);
+        let stats = StreakStats::new(&db.streaks[0], None, today - TimeDelta::days(1));
+        assert_eq!(stats.completion, None);
+        assert_eq!(stats.breaks, None);
+        assert_eq!(stats.average_streak, Some(1.0));
+
+        let streak = Streak {
+            total_checkins: 10,
+            longest_streak: 4,
+            ..Streak::new_daily("Floss".to_string())
+        };
+        let stats = StreakStats::new(&streak, None, today);
+        assert_eq!(stats.average_streak, Some(10.0 / 3.0));
+        let stats = StreakStats::new(&Streak::new_daily("Read".to_string()), None, today);
+        assert_eq!(stats.average_streak, None);
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn backfilled_checkins() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let db_file = temp.child("test_backfilled_checkins.ron");
+        let mut db = Database::new(db_file.to_str().unwrap()).unwrap();
+        db.enable_journal().unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2024, 8, d).unwrap();
+        let mut streak = Streak::new_daily("Walk".to_string());
+        streak.total_checkins = 2;
+        streak.last_checkin = Some(day(3));
+        db.add(streak.clone()).unwrap();
+        db.backfill(streak.id, &[day(1), day(3)]);
+        db.save().unwrap();
+
+        let history = CheckinHistory::load(&db).unwrap().unwrap();
+        assert_eq!(history.dates(streak.id), &[day(1), day(3)]);
+        assert_eq!(history.start(streak.id), Some(day(1)));
+        assert_eq!(history.day_status(&streak, day(2), day(4)), DayStatus::Missed);
+        assert_eq!(db.get_all(), vec![streak]);
+
+        temp.close().unwrap();
+    }
+}
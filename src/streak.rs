@@ -1,8 +1,9 @@
 use std::fmt::Display;
 
+use crate::color::emoji;
 use crate::sorting::{SortByDirection, SortByField};
 #[allow(unused_imports)]
-use chrono::{Local, NaiveDate};
+use chrono::{DateTime, Local, NaiveDate};
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -64,6 +65,14 @@ impl Status {
             Status::Missed => "missed",
         }
     }
+
+    pub fn emoji(&self) -> &'static str {
+        match self {
+            Status::Done => emoji("✅", "[x]"),
+            Status::Missed => emoji("❌", "[!]"),
+            Status::Waiting => emoji("⏳", "[ ]"),
+        }
+    }
 }
 
 impl Display for Status {
@@ -90,6 +99,14 @@ pub struct Streak {
     pub longest_streak: u32,
     #[serde(default)]
     pub total_checkins: u32,
+    /// When the streak was last checked in to or edited
+    #[serde(default)]
+    pub modified_at: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Archived streaks are kept but left out of `list`, `today` and `status`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
 }
 
 impl Streak {
@@ -103,6 +120,9 @@ impl Streak {
             current_streak: 0,
             longest_streak: 0,
             total_checkins: 0,
+            modified_at: Some(Local::now()),
+            tags: vec![],
+            archived: false,
         }
     }
 
@@ -116,11 +136,14 @@ impl Streak {
             current_streak: 0,
             longest_streak: 0,
             total_checkins: 0,
+            modified_at: Some(Local::now()),
+            tags: vec![],
+            archived: false,
         }
     }
 
     pub fn checkin(&mut self) {
-        let date = Local::now().date_naive();
+        let date = crate::config::today();
         if self.last_checkin.is_some() && self.last_checkin.unwrap() == date {
             return;
         }
@@ -130,10 +153,15 @@ impl Streak {
             self.longest_streak = self.current_streak;
         }
         self.total_checkins += 1;
+        self.modified_at = Some(Local::now());
     }
 
     fn was_missed(&self) -> bool {
-        let today = Local::now().date_naive();
+        self.missed_as_of(crate::config::today())
+    }
+
+    /// Whether the streak had already been broken by `today`
+    pub fn missed_as_of(&self, today: NaiveDate) -> bool {
         let duration = match self.last_checkin {
             Some(date) => today - date,
             None => return true,
@@ -145,7 +173,7 @@ impl Streak {
     }
 
     fn done_in_period(&self) -> bool {
-        let today = Local::now().date_naive();
+        let today = crate::config::today();
         let duration = match self.last_checkin {
             Some(date) => today - date,
             None => return false,
@@ -167,11 +195,7 @@ impl Streak {
     }
 
     pub fn emoji_status(&self) -> &str {
-        match self.status() {
-            Status::Done => "✅",
-            Status::Missed => "❌",
-            Status::Waiting => "⏳",
-        }
+        self.status().emoji()
     }
 
     pub fn is_done(&self) -> bool {
@@ -186,6 +210,96 @@ impl Streak {
         self.status() == Status::Waiting
     }
 
+    /// Waiting, with a running streak that breaks unless it's checked in to
+    pub fn is_at_risk(&self) -> bool {
+        self.is_waiting() && self.current_streak > 0
+    }
+
+    /// Combine two copies of the same streak that were changed separately.
+    /// Check-in counts and dates keep whichever is furthest along, and the
+    /// task and frequency come from the copy modified most recently.
+    pub fn merge(&self, other: &Streak) -> Streak {
+        let (older, newer) = match self.modified_at >= other.modified_at {
+            true => (other, self),
+            false => (self, other),
+        };
+        let current_streak = match self.last_checkin.cmp(&other.last_checkin) {
+            std::cmp::Ordering::Greater => self.current_streak,
+            std::cmp::Ordering::Less => other.current_streak,
+            std::cmp::Ordering::Equal => self.current_streak.max(other.current_streak),
+        };
+        Streak {
+            last_checkin: self.last_checkin.max(other.last_checkin),
+            current_streak,
+            longest_streak: self.longest_streak.max(other.longest_streak),
+            total_checkins: self.total_checkins.max(other.total_checkins),
+            modified_at: newer.modified_at.max(older.modified_at),
+            ..newer.clone()
+        }
+    }
+
+    /// Work out the counters from every day the streak was checked in on
+    pub fn count_checkins(&mut self, dates: &[NaiveDate]) {
+        let mut dates = dates.to_vec();
+        dates.sort();
+        dates.dedup();
+
+        let max_gap = match self.frequency {
+            Frequency::Daily => 1,
+            Frequency::Weekly => 7,
+        };
+        self.current_streak = 0;
+        self.longest_streak = 0;
+        self.total_checkins = 0;
+        let mut previous: Option<NaiveDate> = None;
+        for date in dates {
+            self.current_streak = match previous {
+                Some(previous) if (date - previous).num_days() <= max_gap => {
+                    self.current_streak + 1
+                }
+                _ => 1,
+            };
+            self.longest_streak = self.longest_streak.max(self.current_streak);
+            self.total_checkins += 1;
+            previous = Some(date);
+        }
+        self.last_checkin = previous;
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag.trim()))
+    }
+
+    /// Add a tag, returning whether the streak didn't have it yet
+    pub fn add_tag(&mut self, tag: &str) -> bool {
+        if self.has_tag(tag) {
+            return false;
+        }
+        self.tags.push(tag.trim().to_string());
+        self.modified_at = Some(Local::now());
+        true
+    }
+
+    /// Remove a tag, returning whether the streak had it
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        if !self.has_tag(tag) {
+            return false;
+        }
+        self.tags.retain(|t| !t.eq_ignore_ascii_case(tag.trim()));
+        self.modified_at = Some(Local::now());
+        true
+    }
+
+    /// Archive or unarchive the streak, returning whether that changed it
+    pub fn set_archived(&mut self, archived: bool) -> bool {
+        if self.archived == archived {
+            return false;
+        }
+        self.archived = archived;
+        self.modified_at = Some(Local::now());
+        true
+    }
+
     pub fn update(&mut self, new_self: Streak) {
         let id = self.id;
         *self = new_self;
@@ -203,6 +317,9 @@ impl Default for Streak {
             current_streak: 0,
             longest_streak: 0,
             total_checkins: 0,
+            modified_at: Some(Local::now()),
+            tags: vec![],
+            archived: false,
         }
     }
 }
@@ -272,6 +389,17 @@ mod tests {
         assert_eq!(streak.status(), Status::Waiting);
     }
 
+    #[test]
+    fn at_risk() {
+        let mut streak = Streak::new_weekly("Test Streak".to_string());
+        streak.last_checkin = Some(Local::now().date_naive() - TimeDelta::days(6));
+        assert!(!streak.is_at_risk());
+        streak.current_streak = 3;
+        assert!(streak.is_at_risk());
+        streak.last_checkin = Some(Local::now().date_naive());
+        assert!(!streak.is_at_risk());
+    }
+
     #[test]
     fn status_waiting_weekly() {
         let mut streak = Streak::new_weekly("Test Streak".to_string());
@@ -314,6 +442,41 @@ mod tests {
         assert_eq!(streak.total_checkins, 2);
     }
 
+    #[test]
+    fn merge_keeps_newest_edit_and_most_checkins() {
+        let mut ours = Streak::new_daily("Test Streak".to_string());
+        ours.last_checkin = NaiveDate::from_ymd_opt(2020, 4, 21);
+        ours.current_streak = 2;
+        ours.total_checkins = 5;
+
+        let mut theirs = ours.clone();
+        theirs.task = "Renamed".to_string();
+        theirs.last_checkin = NaiveDate::from_ymd_opt(2020, 4, 20);
+        theirs.current_streak = 1;
+        theirs.longest_streak = 7;
+        theirs.modified_at = ours.modified_at.map(|t| t + TimeDelta::minutes(1));
+
+        let merged = ours.merge(&theirs);
+        assert_eq!(merged, theirs.merge(&ours));
+        assert_eq!(merged.task, "Renamed");
+        assert_eq!(merged.last_checkin, NaiveDate::from_ymd_opt(2020, 4, 21));
+        assert_eq!(merged.current_streak, 2);
+        assert_eq!(merged.longest_streak, 7);
+        assert_eq!(merged.total_checkins, 5);
+        assert_eq!(merged.modified_at, theirs.modified_at);
+    }
+
+    #[test]
+    fn tags() {
+        let mut streak = Streak::new_daily("Test Streak".to_string());
+        assert!(streak.add_tag("morning"));
+        assert!(!streak.add_tag(" Morning "));
+        assert!(streak.has_tag("MORNING"));
+        assert!(streak.remove_tag("Morning"));
+        assert!(!streak.remove_tag("morning"));
+        assert!(streak.tags.is_empty());
+    }
+
     #[test]
     fn daily_streak_broken() {
         let old_date = NaiveDate::from_ymd_opt(2020, 4, 20).unwrap();
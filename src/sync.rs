@@ -0,0 +1,260 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{Database, MergeSummary};
+
+/// Remote that changes are pulled from and pushed to, if the repository has one
+const REMOTE: &str = "origin";
+
+/// What a sync did
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SyncSummary {
+    /// Streaks merged in from the remote copy, if there was one
+    pub merged: Option<MergeSummary>,
+    pub committed: bool,
+    pub pushed: bool,
+}
+
+/// Keeps a copy of the database in a git repository and syncs it through
+/// the repository's `origin` remote. Remote changes are merged streak by
+/// streak rather than line by line, so git never sees a conflict in the file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GitSync {
+    pub repo: PathBuf,
+    file_name: String,
+}
+
+impl GitSync {
+    fn config_path(db_filename: &str) -> PathBuf {
+        Path::new(db_filename).with_extension("sync.ron")
+    }
+
+    /// The sync settings for a database, if sync has been set up
+    pub fn open(db_filename: &str) -> Option<Self> {
+        std::fs::read_to_string(Self::config_path(db_filename))
+            .ok()
+            .and_then(|contents| ron::de::from_str(&contents).ok())
+    }
+
+    /// Sync a database through a repository, creating the repository if needed
+    pub fn init(db_filename: &str, repo: &Path) -> Result<Self, std::io::Error> {
+        std::fs::create_dir_all(repo)?;
+        let file_name = Path::new(db_filename)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "skidmarks.ron".to_string());
+        let sync = Self {
+            repo: repo.canonicalize()?,
+            file_name,
+        };
+        if !sync.run(&["rev-parse", "--git-dir"])?.status.success() {
+            sync.git(&["init", "--quiet"])?;
+        }
+
+        let encoded = ron::ser::to_string(&sync)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        std::fs::write(Self::config_path(db_filename), encoded)?;
+        Ok(sync)
+    }
+
+    fn run(&self, args: &[&str]) -> Result<Output, std::io::Error> {
+        Command::new("git")
+            .arg("-C")
+            .arg(&self.repo)
+            .args(args)
+            .output()
+    }
+
+    /// Run git in the repository, turning a failure into an error with git's message
+    fn git(&self, args: &[&str]) -> Result<String, std::io::Error> {
+        let output = self.run(args)?;
+        match output.status.success() {
+            true => Ok(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+            false => Err(std::io::Error::other(format!(
+                "git {} failed: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))),
+        }
+    }
+
+    /// Like `git`, but with a fallback identity for repositories without one
+    fn git_as_user(&self, args: &[&str]) -> Result<String, std::io::Error> {
+        let mut full_args = vec![];
+        if self.git(&["config", "user.email"]).is_err() {
+            full_args.extend([
+                "-c",
+                "user.name=skidmarks",
+                "-c",
+                "user.email=skidmarks@localhost",
+            ]);
+        }
+        full_args.extend(args);
+        self.git(&full_args)
+    }
+
+    fn check_conflicts(&self) -> Result<(), std::io::Error> {
+        let conflicts = self.git(&["diff", "--name-only", "--diff-filter=U"])?;
+        match conflicts.is_empty() {
+            true => Ok(()),
+            false => Err(std::io::Error::other(format!(
+                "The sync repository has unresolved conflicts in: {}",
+                conflicts.lines().collect::<Vec<&str>>().join(", ")
+            ))),
+        }
+    }
+
+    /// Merge the remote copy of the database into `db` and record the merge in git
+    fn pull(
+        &self,
+        db: &mut Database,
+        upstream: &str,
+    ) -> Result<Option<MergeSummary>, std::io::Error> {
+        let path = format!("{upstream}:{}", self.file_name);
+        let output = self.run(&["show", &path])?;
+        let summary = match output.status.success() {
+            true => {
+                let incoming =
+                    std::env::temp_dir().join(format!("skidmarks-sync-{}.ron", Uuid::new_v4()));
+                std::fs::write(&incoming, output.stdout)?;
+                let other = db.reopen(&incoming.to_string_lossy());
+                std::fs::remove_file(&incoming)?;
+                Some(db.merge_streaks(other?.streaks, None)?)
+            }
+            false => None,
+        };
+
+        let has_head = self
+            .run(&["rev-parse", "--verify", "--quiet", "HEAD"])?
+            .status
+            .success();
+        if !has_head {
+            self.git(&["reset", "--quiet", upstream])?;
+        } else if !self
+            .run(&["merge-base", "--is-ancestor", upstream, "HEAD"])?
+            .status
+            .success()
+        {
+            self.git_as_user(&[
+                "merge",
+                "--quiet",
+                "--strategy",
+                "ours",
+                "--no-edit",
+                upstream,
+            ])?;
+        }
+        Ok(summary)
+    }
+
+    /// Pull and merge remote changes, commit the database and push it back
+    pub fn sync(&self, db: &mut Database) -> Result<SyncSummary, std::io::Error> {
+        self.check_conflicts()?;
+        let mut summary = SyncSummary::default();
+
+        let has_remote = self
+            .git(&["remote"])?
+            .lines()
+            .any(|remote| remote == REMOTE);
+        let branch = self.git(&["symbolic-ref", "--short", "HEAD"])?;
+        if has_remote {
+            self.git(&["fetch", "--quiet", REMOTE])?;
+            let upstream = format!("{REMOTE}/{branch}");
+            let verify = ["rev-parse", "--verify", "--quiet", upstream.as_str()];
+            if self.run(&verify)?.status.success() {
+                summary.merged = self.pull(db, &upstream)?;
+            }
+        }
+
+        db.save()?;
+        std::fs::copy(&db.filename, self.repo.join(&self.file_name))?;
+        self.git(&["add", &self.file_name])?;
+        if !self.run(&["diff", "--cached", "--quiet"])?.status.success() {
+            self.git_as_user(&["commit", "--quiet", "--message", "Update streaks"])?;
+            summary.committed = true;
+        }
+
+        if has_remote {
+            let refspec = format!("HEAD:{branch}");
+            self.git(&["push", "--quiet", REMOTE, &refspec])
+                .map_err(|err| {
+                    std::io::Error::other(format!(
+                        "{err}. Someone else pushed first, sync again to merge their changes"
+                    ))
+                })?;
+            summary.pushed = true;
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+
+    use super::*;
+    use crate::streak::Streak;
+
+    fn clone(remote: &Path, to: &Path) {
+        let status = Command::new("git")
+            .args(["clone", "--quiet"])
+            .arg(remote)
+            .arg(to)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    fn sync_through_bare_repo() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let remote = temp.child("remote.git");
+        let status = Command::new("git")
+            .args(["init", "--quiet", "--bare"])
+            .arg(remote.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        clone(remote.path(), temp.child("laptop").path());
+        clone(remote.path(), temp.child("desktop").path());
+
+        let laptop_file = temp.child("laptop-data/skidmarks.ron");
+        temp.child("laptop-data").create_dir_all().unwrap();
+        let mut laptop = Database::new(laptop_file.to_str().unwrap()).unwrap();
+        laptop.add(Streak::new_daily("Poop".to_string())).unwrap();
+        let laptop_sync = GitSync::init(&laptop.filename, temp.child("laptop").path()).unwrap();
+        let summary = laptop_sync.sync(&mut laptop).unwrap();
+        assert!(summary.committed && summary.pushed);
+        assert_eq!(summary.merged, None);
+
+        let desktop_file = temp.child("desktop-data/skidmarks.ron");
+        temp.child("desktop-data").create_dir_all().unwrap();
+        let mut desktop = Database::new(desktop_file.to_str().unwrap()).unwrap();
+        let desktop_sync = GitSync::init(&desktop.filename, temp.child("desktop").path()).unwrap();
+        assert_eq!(GitSync::open(&desktop.filename), Some(desktop_sync.clone()));
+        let summary = desktop_sync.sync(&mut desktop).unwrap();
+        assert_eq!(summary.merged.unwrap().added.len(), 1);
+        assert_eq!(desktop.num_tasks(), 1);
+
+        desktop.checkin(desktop.streaks[0].id).unwrap();
+        laptop
+            .add(Streak::new_weekly("Stretch".to_string()))
+            .unwrap();
+        desktop_sync.sync(&mut desktop).unwrap();
+        let summary = laptop_sync.sync(&mut laptop).unwrap();
+        let merged = summary.merged.unwrap();
+        assert_eq!(merged.updated.len(), 1);
+        assert!(summary.committed && summary.pushed);
+        assert_eq!(laptop.num_tasks(), 2);
+        assert_eq!(laptop.streaks[0].total_checkins, 1);
+
+        let summary = desktop_sync.sync(&mut desktop).unwrap();
+        assert_eq!(summary.merged.unwrap().added.len(), 1);
+        assert_eq!(desktop.num_tasks(), 2);
+
+        temp.close().unwrap();
+    }
+}
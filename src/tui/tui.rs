@@ -1,17 +1,22 @@
-use crate::cli::get_database_url;
-use crate::color::TuiStyles;
+use crate::color::{Theme, TuiStyles};
+use crate::config::{self, Config};
 use crate::db::Database;
 use crate::filtering::{filter_by_status, FilterByStatus};
+use crate::profile::Profiles;
 use crate::sorting::{SortByDirection, SortByField};
+use crate::stats::{month_weeks, week_header, CheckinHistory};
 use crate::streak::{Frequency, Streak};
+use chrono::{Datelike, Months, NaiveDate};
 use ratatui::widgets::{
-    Block, BorderType, Borders, Cell, HighlightSpacing, Paragraph, Row, Scrollbar,
+    Block, BorderType, Borders, Cell, Clear, HighlightSpacing, Paragraph, Row, Scrollbar,
     ScrollbarOrientation, ScrollbarState, Table, TableState, Tabs,
 };
 use ratatui::{
     backend::{Backend, CrosstermBackend},
     crossterm::{
-        event::{self, DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEventKind},
+        event::{
+            self, DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEventKind, KeyModifiers,
+        },
         execute,
         terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     },
@@ -22,6 +27,7 @@ use ratatui::{
 };
 use std::io;
 use term_size::dimensions;
+use uuid::Uuid;
 
 #[derive(Clone, Debug)]
 struct NewStreak {
@@ -44,6 +50,15 @@ enum AppState {
     Insert,
     Search,
     Delete,
+    Month,
+}
+
+/// The month popup: which streak and month it shows, and the history it's drawn from
+#[derive(Clone, Debug)]
+struct MonthView {
+    streak: Streak,
+    first: NaiveDate,
+    history: CheckinHistory,
 }
 
 #[derive(Clone, Debug)]
@@ -58,24 +73,39 @@ struct App {
     tab_state: u8,
     search_phrase: String,
     new_streak: NewStreak,
+    theme: Theme,
     styles: TuiStyles,
+    error: Option<String>,
+    profile: Option<String>,
+    month_view: Option<MonthView>,
 }
 
 impl App {
-    pub fn new() -> Self {
-        let db = Database::new(&get_database_url()).unwrap();
+    pub fn new(db: Database, profile: Option<String>) -> Self {
+        let config = Config::current();
+        let (sort_by_field, sort_by_direction) = config.sort_order();
         App {
             app_state: AppState::Normal,
             table_state: TableState::default().with_selected(0),
             scrollbar_state: ScrollbarState::new(db.num_tasks()).position(0),
             db,
-            sort_by_field: SortByField::Task,
-            sort_by_direction: SortByDirection::Ascending,
-            filter_by_status: FilterByStatus::All,
-            tab_state: 0,
+            sort_by_field,
+            sort_by_direction,
+            filter_by_status: config.filter(),
+            // Tabs go All, Waiting, Missed, Done, the same order `f` cycles through
+            tab_state: match config.filter() {
+                FilterByStatus::All => 0,
+                FilterByStatus::Waiting => 1,
+                FilterByStatus::Missed => 2,
+                FilterByStatus::Done => 3,
+            },
             search_phrase: String::default(),
             new_streak: NewStreak::default(),
+            theme: Theme::current().clone(),
             styles: TuiStyles::new(),
+            error: None,
+            profile,
+            month_view: None,
         }
     }
 
@@ -109,17 +139,49 @@ impl App {
         self.scrollbar_state = self.scrollbar_state.position(i);
     }
 
-    pub fn check_in(&mut self) -> io::Result<()> {
-        let i = self.table_state.selected().unwrap();
-        let mut streak = self
+    /// Streaks as currently shown, with the sort, filter and search applied
+    fn visible_streaks(&self) -> Vec<Streak> {
+        let streaks = self
             .db
-            .get_by_index(
-                i,
-                self.sort_by_field.clone(),
-                self.sort_by_direction.clone(),
-                self.filter_by_status.clone(),
-            )
-            .unwrap();
+            .get_sorted(self.sort_by_field.clone(), self.sort_by_direction.clone());
+        let mut streaks = filter_by_status(streaks, self.filter_by_status.clone());
+        streaks.retain(|streak| !streak.archived);
+        if !self.search_phrase.is_empty() {
+            let search_phrase = self.search_phrase.to_lowercase();
+            streaks.retain(|streak| streak.task.to_lowercase().contains(&search_phrase));
+        }
+        streaks
+    }
+
+    fn selected_streak(&self) -> Option<Streak> {
+        let i = self.table_state.selected()?;
+        self.visible_streaks().get(i).cloned()
+    }
+
+    /// Reload the database from disk, keeping the same streak selected
+    pub fn reload(&mut self) -> io::Result<()> {
+        let selected = self.selected_streak().map(|streak| streak.id);
+        self.db.reload()?;
+        self.reselect(selected);
+        Ok(())
+    }
+
+    /// Keep the selection on the streak that had it, or at the same row if
+    /// it's gone, after the list changed underneath
+    fn reselect(&mut self, selected: Option<Uuid>) {
+        let streaks = self.visible_streaks();
+        let i = selected
+            .and_then(|id| streaks.iter().position(|streak| streak.id == id))
+            .or(self.table_state.selected())
+            .map(|i| i.min(streaks.len().saturating_sub(1)));
+        self.table_state.select(i);
+        self.scrollbar_state = self.scrollbar_state.content_length(streaks.len());
+    }
+
+    pub fn check_in(&mut self) -> io::Result<()> {
+        let Some(mut streak) = self.selected_streak() else {
+            return Ok(());
+        };
         streak.checkin();
         self.db.update(streak.id, streak)?;
         self.db.save()?;
@@ -136,30 +198,94 @@ impl App {
         Ok(())
     }
 
+    /// Move on to the next profile's streaks
+    pub fn switch_profile(&mut self) -> io::Result<()> {
+        let profiles = Profiles::new();
+        let profile = match &self.profile {
+            Some(profile) => profiles.next(profile),
+            None => profiles.next(""),
+        };
+        self.db = self.db.reopen(&profiles.path(&profile).to_string_lossy())?;
+        self.profile = Some(profile);
+        self.table_state.select(Some(0));
+        self.scrollbar_state = ScrollbarState::new(self.db.num_tasks()).position(0);
+        Ok(())
+    }
+
+    pub fn undo(&mut self) -> io::Result<()> {
+        let selected = self.selected_streak().map(|streak| streak.id);
+        self.db.undo()?;
+        self.reselect(selected);
+        Ok(())
+    }
+
+    pub fn redo(&mut self) -> io::Result<()> {
+        let selected = self.selected_streak().map(|streak| streak.id);
+        self.db.redo()?;
+        self.reselect(selected);
+        Ok(())
+    }
+
+    /// Open the month popup for the selected streak at the current month
+    pub fn open_month(&mut self) -> io::Result<()> {
+        let Some(streak) = self.selected_streak() else {
+            return Ok(());
+        };
+        let Some(history) = CheckinHistory::load(&self.db)? else {
+            return Err(io::Error::other(
+                "Turn on the journal with `skidmarks journal enable` to see past check-ins",
+            ));
+        };
+        let today = config::today();
+        self.month_view = Some(MonthView {
+            streak,
+            first: today.with_day(1).unwrap_or(today),
+            history,
+        });
+        self.app_state = AppState::Month;
+        Ok(())
+    }
+
+    /// Switch to the next theme, for this session only
+    pub fn cycle_theme(&mut self) -> Result<(), String> {
+        let theme = Theme::load(&self.theme.next_name())?;
+        self.styles = TuiStyles::from_theme(&theme);
+        self.theme = theme;
+        Ok(())
+    }
+
+    /// Move the month popup by a number of months, or back to this month with 0
+    pub fn shift_month(&mut self, months: i32) {
+        let Some(view) = self.month_view.as_mut() else {
+            return;
+        };
+        let today = config::today();
+        view.first = match months {
+            0 => today.with_day(1),
+            n if n > 0 => view.first.checked_add_months(Months::new(n as u32)),
+            n => view.first.checked_sub_months(Months::new(n.unsigned_abs())),
+        }
+        .unwrap_or(view.first);
+    }
+
     pub fn delete_selected(&mut self) -> io::Result<()> {
-        let i = self.table_state.selected().unwrap();
-        let streak = self
-            .db
-            .get_by_index(
-                i,
-                self.sort_by_field.clone(),
-                self.sort_by_direction.clone(),
-                self.filter_by_status.clone(),
-            )
-            .unwrap();
+        let Some(streak) = self.selected_streak() else {
+            return Ok(());
+        };
         self.db.delete(streak.id)?;
         self.db.save()?;
+        self.reselect(None);
         Ok(())
     }
 }
 
-pub fn main() -> io::Result<()> {
+pub fn main(db: Database, profile: Option<String>) -> io::Result<()> {
     enable_raw_mode()?;
     execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
     let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
     terminal.clear()?;
 
-    let mut app = App::new();
+    let mut app = App::new(db, profile);
     let res = run_app(&mut terminal, &mut app);
 
     disable_raw_mode()?;
@@ -177,7 +303,20 @@ pub fn main() -> io::Result<()> {
 }
 
 fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: &mut App) -> io::Result<()> {
+    let mut watched = app.db.filename.clone();
+    let mut watcher = app.db.watch().ok();
     loop {
+        // Pick up changes made by other processes
+        if app.db.filename != watched {
+            watched = app.db.filename.clone();
+            watcher = app.db.watch().ok();
+        }
+        if watcher.as_ref().is_some_and(|watcher| watcher.changed()) {
+            if let Err(err) = app.reload() {
+                app.error = Some(err.to_string());
+            }
+        }
+
         // Draw the UI
         terminal.draw(|frame| {
             let _ = layout_app(&mut app, frame);
@@ -187,12 +326,32 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: &mut App) -> io::Res
         if event::poll(std::time::Duration::from_millis(16))? {
             if let event::Event::Key(key) = event::read()? {
                 if key.kind == KeyEventKind::Press {
+                    app.error = None;
                     match app.app_state {
                         AppState::Normal => match key.code {
                             KeyCode::Char('q') => break,
                             KeyCode::Char('j') => app.select_down(),
                             KeyCode::Char('k') => app.select_up(),
-                            KeyCode::Char('c') => app.check_in()?,
+                            KeyCode::Char('c') => {
+                                if let Err(err) = app.check_in() {
+                                    app.error = Some(err.to_string());
+                                }
+                            }
+                            KeyCode::Char('p') => {
+                                if let Err(err) = app.switch_profile() {
+                                    app.error = Some(err.to_string());
+                                }
+                            }
+                            KeyCode::Char('u') => {
+                                if let Err(err) = app.undo() {
+                                    app.error = Some(err.to_string());
+                                }
+                            }
+                            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                if let Err(err) = app.redo() {
+                                    app.error = Some(err.to_string());
+                                }
+                            }
                             KeyCode::Char('z') => match app.sort_by_direction {
                                 SortByDirection::Ascending => {
                                     app.sort_by_direction = SortByDirection::Descending
@@ -245,12 +404,34 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: &mut App) -> io::Res
                             KeyCode::Char('d') => {
                                 app.app_state = AppState::Delete;
                             }
+                            KeyCode::Char('m') => {
+                                if let Err(err) = app.open_month() {
+                                    app.error = Some(err.to_string());
+                                }
+                            }
+                            KeyCode::Char('t') => {
+                                if let Err(err) = app.cycle_theme() {
+                                    app.error = Some(err);
+                                }
+                            }
+                            _ => {}
+                        },
+                        AppState::Month => match key.code {
+                            KeyCode::Char('h') | KeyCode::Left => app.shift_month(-1),
+                            KeyCode::Char('l') | KeyCode::Right => app.shift_month(1),
+                            KeyCode::Char('t') => app.shift_month(0),
+                            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('m') => {
+                                app.month_view = None;
+                                app.app_state = AppState::Normal;
+                            }
                             _ => {}
                         },
                         AppState::Insert => match key.code {
                             KeyCode::Esc => app.app_state = AppState::Normal,
                             KeyCode::Enter => {
-                                app.add_streak()?;
+                                if let Err(err) = app.add_streak() {
+                                    app.error = Some(err.to_string());
+                                }
                                 app.app_state = AppState::Normal;
                             }
                             KeyCode::Backspace => {
@@ -281,7 +462,9 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: &mut App) -> io::Res
                         },
                         AppState::Delete => match key.code {
                             KeyCode::Char('y') => {
-                                app.delete_selected()?;
+                                if let Err(err) = app.delete_selected() {
+                                    app.error = Some(err.to_string());
+                                }
                                 app.app_state = AppState::Normal;
                             }
                             KeyCode::Char('n') => {
@@ -314,6 +497,7 @@ fn layout_app(app: &mut App, frame: &mut Frame) -> io::Result<()> {
         AppState::Search => layout_search(app, frame, chunks[1])?,
         AppState::Insert => layout_add(app, frame, chunks[1])?,
         AppState::Delete => layout_delete(app, frame, chunks[1])?,
+        AppState::Month => layout_month(app, frame, chunks[1])?,
         _ => layout_main(app, frame, chunks[1])?,
     }
 
@@ -327,7 +511,10 @@ fn draw_header(app: &mut App, frame: &mut Frame, area: Rect) -> io::Result<()> {
         .borders(Borders::BOTTOM)
         .border_type(BorderType::Thick)
         .bg(app.styles.background);
-    let text = "Skidmarks";
+    let text = match &app.profile {
+        Some(profile) => format!("Skidmarks · {profile}"),
+        None => "Skidmarks".to_string(),
+    };
     let paragraph = Paragraph::new(text)
         .alignment(Alignment::Center)
         .block(block);
@@ -341,14 +528,17 @@ fn draw_footer(app: &mut App, frame: &mut Frame, area: Rect) -> io::Result<()> {
         .borders(Borders::TOP)
         .border_type(BorderType::Thick);
     let text = match app.app_state {
-        AppState::Normal => "[f] filter, [o] change sort order, [z] reverse order, [s] search\n[j/k] select, [c] check in, [a] add, [d] delete, [q] quit",
+        AppState::Normal => "[f] filter, [o] change sort order, [z] reverse order, [s] search, [p] profile\n[j/k] select, [c] check in, [a] add, [d] delete, [m] month, [t] theme, [u/Ctrl-r] undo/redo, [q] quit",
         AppState::Insert => "[Esc] cancel, [Enter] save, [Tab] toggle frequency",
         AppState::Search => "[Esc] cancel, [Enter] search, [Backspace] delete",
         AppState::Delete => "[y] yes, [n] no",
+        AppState::Month => "[h/l] previous/next month, [t] this month, [Esc] close",
     };
-    let help_text = Paragraph::new(text)
-        .alignment(Alignment::Center)
-        .block(block);
+    let help_text = match &app.error {
+        Some(error) => Paragraph::new(error.as_str()).fg(app.styles.danger),
+        None => Paragraph::new(text),
+    };
+    let help_text = help_text.alignment(Alignment::Center).block(block);
     frame.render_widget(help_text, area);
     Ok(())
 }
@@ -477,25 +667,9 @@ fn draw_table(app: &mut App, frame: &mut Frame, area: Rect) -> io::Result<()> {
 }
 
 fn get_rows(app: &mut App) -> Vec<Row<'static>> {
-    let app = app.clone();
-    let database = Database::new(&get_database_url());
-    let streaks = database
-        .unwrap()
-        .get_sorted(app.sort_by_field, app.sort_by_direction);
-    let mut streaks = filter_by_status(streaks, app.filter_by_status);
-    if !app.search_phrase.is_empty() {
-        streaks = streaks
-            .into_iter()
-            .filter(|streak| {
-                streak
-                    .task
-                    .to_lowercase()
-                    .contains(&app.search_phrase.to_lowercase())
-            })
-            .collect();
-    }
+    let streaks = app.visible_streaks();
 
-    let styles = TuiStyles::new();
+    let styles = app.styles.clone();
 
     let mut rows = vec![];
     let (w, _) = dimensions().unwrap();
@@ -511,7 +685,7 @@ fn get_rows(app: &mut App) -> Vec<Row<'static>> {
         let status = Text::from(status).alignment(Alignment::Center);
         let last_checkin = streak
             .last_checkin
-            .map(|dt| dt.format("%Y-%m-%d").to_string())
+            .map(|dt| dt.format(Config::current().date_format()).to_string())
             .unwrap_or("None".to_string());
         let current_streak =
             Text::from(streak.current_streak.to_string()).alignment(Alignment::Center);
@@ -644,3 +818,58 @@ fn layout_delete(app: &mut App, frame: &mut Frame, area: Rect) -> io::Result<()>
 
     Ok(())
 }
+
+fn layout_month(app: &mut App, frame: &mut Frame, area: Rect) -> io::Result<()> {
+    let Some(view) = &app.month_view else {
+        return Ok(());
+    };
+    let today = config::today();
+    let week_start = Config::current().week_start();
+    let weeks = month_weeks(view.first, week_start);
+    let mut lines = vec![Line::from(week_header(week_start)).fg(app.styles.highlight_bg)];
+    for week in weeks {
+        let mut numbers = String::new();
+        let mut markers = String::new();
+        for day in week {
+            match day {
+                Some(day) => {
+                    let status = view.history.day_status(&view.streak, day, today);
+                    numbers.push_str(&format!("{:>2}  ", day.day()));
+                    markers.push_str(&format!("{}  ", status.marker()));
+                }
+                None => {
+                    numbers.push_str("    ");
+                    markers.push_str("    ");
+                }
+            }
+        }
+        lines.push(Line::from(numbers.trim_end().to_string()));
+        lines.push(Line::from(markers.trim_end().to_string()));
+    }
+
+    let height = lines.len() as u16 + 2;
+    let layout = get_centered_layout(height).split(area);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Fill(1),
+            Constraint::Length(32),
+            Constraint::Fill(1),
+        ])
+        .split(layout[1]);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.styles.highlight_bg))
+        .title(format!(
+            "{} · {}",
+            view.streak.task,
+            view.first.format("%B %Y")
+        ))
+        .title_alignment(Alignment::Center);
+    let paragraph = Paragraph::new(lines)
+        .style(Style::default().fg(app.styles.foreground))
+        .block(block);
+    frame.render_widget(Clear, columns[1]);
+    frame.render_widget(paragraph, columns[1]);
+    Ok(())
+}